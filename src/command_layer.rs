@@ -0,0 +1,143 @@
+use std::{
+    io::Read,
+    process::{Child, Command, Stdio},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::Format;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A layer running a program and parsing its standard output
+#[derive(Clone)]
+pub struct CommandLayer {
+    program: String,
+    args: Vec<String>,
+    // when missing, the output is parsed as JSON if possible, and as YAML otherwise
+    format: Option<Format>,
+    timeout: Duration,
+}
+
+impl CommandLayer {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            format: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format.replace(format);
+        self
+    }
+
+    /// Sets how long the program may run before it is killed, which defaults to 5 seconds
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .map(|part| format!("{part:?}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // A non-zero exit status or running past the timeout fails the load. The timeout also covers
+    // reading the output, which may be held open by processes the program left running
+    pub(crate) fn load(&self) -> Result<Vec<Value>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // the pipes are drained in the background, so that the program never blocks on writing
+        let stdout = read_in_background(child.stdout.take());
+        let stderr = read_in_background(child.stderr.take());
+
+        let status = wait_until(&mut child, deadline, self.timeout)?;
+        let stdout = receive_output(&stdout, deadline, self.timeout)?;
+        if !status.success() {
+            let stderr = receive_output(&stderr, deadline, self.timeout).unwrap_or_default();
+            bail!("Command exited with {status}: {}", stderr.trim());
+        }
+
+        match self.format {
+            Some(format) => format.parse(&stdout),
+            None => Format::sniff_and_parse(&stdout),
+        }
+    }
+}
+
+fn wait_until(
+    child: &mut Child,
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<std::process::ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("Command timed out after {timeout:?}");
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn read_in_background(
+    pipe: Option<impl Read + Send + 'static>,
+) -> Receiver<std::io::Result<String>> {
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+        let mut returned = String::new();
+        let result = match pipe {
+            Some(mut pipe) => pipe.read_to_string(&mut returned).map(|_| returned),
+            None => Ok(returned),
+        };
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+// Readers still blocked past the deadline are left behind, and finish once the pipe is closed
+fn receive_output(
+    reader: &Receiver<std::io::Result<String>>,
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<String> {
+    match reader.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(output) => output.context("Failed reading command output"),
+        Err(RecvTimeoutError::Timeout) => {
+            bail!("Command output was still open after the {timeout:?} timeout")
+        }
+        Err(RecvTimeoutError::Disconnected) => bail!("Reading command output panicked"),
+    }
+}
@@ -0,0 +1,52 @@
+use std::{io::Read, path::Path};
+
+use anyhow::{bail, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Self::Gzip),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    // Unknown encodings are reported rather than having the encoded body parsed as-is
+    pub(crate) fn from_content_encoding(content_encoding: &str) -> Result<Option<Self>> {
+        Ok(
+            match content_encoding.trim().to_ascii_lowercase().as_str() {
+                "" | "identity" => None,
+                "gzip" | "x-gzip" => Some(Self::Gzip),
+                "zstd" => Some(Self::Zstd),
+                other => bail!("Unsupported Content-Encoding {other:?}"),
+            },
+        )
+    }
+
+    pub(crate) fn decompress(self, compressed: &[u8]) -> Result<Vec<u8>> {
+        let mut returned = Vec::new();
+        match self {
+            Self::Gzip => {
+                flate2::read::MultiGzDecoder::new(compressed).read_to_end(&mut returned)?;
+            }
+            Self::Zstd => {
+                zstd::stream::read::Decoder::new(compressed)?.read_to_end(&mut returned)?;
+            }
+        }
+        Ok(returned)
+    }
+}
+
+// The path a compressed file would have had uncompressed, used to tell its format by extension
+pub(crate) fn uncompressed_path(path: &Path) -> &Path {
+    match Compression::from_path(path) {
+        Some(_) => path.file_stem().map_or(path, Path::new),
+        None => path,
+    }
+}
@@ -1,26 +1,30 @@
-use anyhow::{format_err, Context, Result};
+use anyhow::{Context, Result};
 use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{path::PathBuf, sync::Arc};
 
-use crate::config_loader::ConfigLoader;
+use crate::{config_loader::ConfigLoader, references::resolve_references, Format};
 
 struct ConfigInner<T: Send + Sync> {
     built: T,
     raw: Value,
     overlay: Value,
+    profile: Option<String>,
 }
 
 impl<T> ConfigInner<T>
 where
     T: Send + Sync + for<'de> Deserialize<'de>,
 {
-    fn rebuild(&mut self) -> Result<()> {
-        let mut to_build = self.raw.clone();
-        json_patch::merge(&mut to_build, &self.overlay);
-
-        self.built = serde_json::from_value(to_build)?;
+    // Builds from the given raw value and overlay, which are only kept if building succeeds
+    fn rebuild(&mut self, raw: Value, overlay: Value) -> Result<()> {
+        let mut to_build = raw.clone();
+        json_patch::merge(&mut to_build, &overlay);
+
+        self.built = serde_json::from_value(resolve_references(&to_build)?)?;
+        self.raw = raw;
+        self.overlay = overlay;
         Ok(())
     }
 }
@@ -45,11 +49,28 @@ where
         ConfigLoader::new().and_overlay_json(path)
     }
 
+    pub fn load_json5_file(path: impl Into<PathBuf>) -> ConfigLoader<T> {
+        ConfigLoader::new().and_overlay_json5(path)
+    }
+
     pub fn load_yaml_file(path: impl Into<PathBuf>) -> ConfigLoader<T> {
         ConfigLoader::new().and_overlay_yaml(path)
     }
 
-    // Gets a raw value by its path
+    pub fn load_toml_file(path: impl Into<PathBuf>) -> ConfigLoader<T> {
+        ConfigLoader::new().and_overlay_toml(path)
+    }
+
+    pub fn load_file(path: impl Into<PathBuf>) -> ConfigLoader<T> {
+        ConfigLoader::new().and_overlay_file(path)
+    }
+
+    pub fn load_str(format: Format, contents: &'static str) -> ConfigLoader<T> {
+        ConfigLoader::new().and_overlay_str(format, contents)
+    }
+
+    // Gets a raw value by its path. Raw values hold references as written, so `${.a.b}`
+    // placeholders (and `$${...}` escapes) are returned unresolved
     pub fn get_raw<V>(&self, path: &str) -> Result<V>
     where
         V: for<'de> Deserialize<'de>,
@@ -76,19 +97,15 @@ where
     where
         V: Serialize,
     {
-        let mut parts = path.split('.').rev();
-        let attr = parts
-            .next()
-            .ok_or_else(|| format_err!("Invalid attribute string"))?;
-        let mut patch = json!({attr: serde_json::to_value(&value)?});
-
-        for part in parts {
-            patch = json!({ part: patch });
-        }
-        self.merge(patch)
+        self.merge(nest_under(path.split('.'), serde_json::to_value(&value)?))
     }
 
-    pub fn get(&self) -> MappedRwLockReadGuard<T> {
+    /// Gets the name of the profile selected when loading, if any
+    pub fn profile(&self) -> Option<String> {
+        self.read_inner().profile.clone()
+    }
+
+    pub fn get(&self) -> MappedRwLockReadGuard<'_, T> {
         RwLockReadGuard::map(self.inner.read(), |inner| &inner.built)
     }
 
@@ -96,33 +113,42 @@ where
         self.merge_and_keep_locked(patch).map(drop)
     }
 
-    fn merge_and_keep_locked(&self, patch: Value) -> Result<RwLockWriteGuard<ConfigInner<T>>> {
+    fn merge_and_keep_locked(&self, patch: Value) -> Result<RwLockWriteGuard<'_, ConfigInner<T>>> {
         let mut locked = self.write_inner();
         let mut new_overlay = locked.overlay.clone();
         json_patch::merge(&mut new_overlay, &patch);
-        locked.overlay = new_overlay;
-        locked.rebuild()?;
+        let raw = locked.raw.clone();
+        locked.rebuild(raw, new_overlay)?;
         Ok(locked)
     }
 
-    fn read_inner(&self) -> RwLockReadGuard<ConfigInner<T>> {
+    fn read_inner(&self) -> RwLockReadGuard<'_, ConfigInner<T>> {
         self.inner.read()
     }
 
-    fn write_inner(&self) -> RwLockWriteGuard<ConfigInner<T>> {
+    fn write_inner(&self) -> RwLockWriteGuard<'_, ConfigInner<T>> {
         self.inner.write()
     }
 
     pub fn replace_raw(&self, value: Value) -> Result<()> {
         let raw = serde_json::to_value(value)?;
         let mut locked = self.write_inner();
-        locked.raw = raw;
-        locked.rebuild()?;
+        let overlay = locked.overlay.clone();
+        locked.rebuild(raw, overlay)?;
 
         Ok(())
     }
 }
 
+// Builds a merge patch placing `value` under the given sequence of keys
+pub(crate) fn nest_under<'a>(parts: impl IntoIterator<Item = &'a str>, value: Value) -> Value {
+    let parts: Vec<_> = parts.into_iter().collect();
+    parts
+        .into_iter()
+        .rev()
+        .fold(value, |patch, part| json!({ part: patch }))
+}
+
 impl Config<Value> {
     pub fn empty() -> Self {
         Self::new_with(json!({})).unwrap()
@@ -133,25 +159,26 @@ impl<T> Config<T>
 where
     T: for<'de> Deserialize<'de> + Serialize + Send + Sync,
 {
+    // References are resolved here too, so that the value built matches what later changes rebuild
     pub fn new_with(built: T) -> Result<Self> {
-        let raw = serde_json::to_value(&built)?;
-        Ok(Self::new_with_raw_and_built(raw, built))
+        Self::new_with_raw(serde_json::to_value(&built)?, None)
     }
 
-    pub(crate) fn new_with_raw(raw: Value) -> Result<Self> {
-        let built = serde_json::from_value(raw.clone()).with_context(|| {
+    pub(crate) fn new_with_raw(raw: Value, profile: Option<String>) -> Result<Self> {
+        let built = serde_json::from_value(resolve_references(&raw)?).with_context(|| {
             format!("Failed loading from configuration. Attempted configuration: {raw:?}")
         })?;
-        Ok(Self::new_with_raw_and_built(raw, built))
+        Ok(Self::new_with_raw_and_built(raw, built, profile))
     }
 
-    fn new_with_raw_and_built(raw: Value, built: T) -> Self {
+    fn new_with_raw_and_built(raw: Value, built: T, profile: Option<String>) -> Self {
         let overlay = json!({});
         Self {
             inner: Arc::new(RwLock::new(ConfigInner {
                 built,
                 raw,
                 overlay,
+                profile,
             })),
         }
     }
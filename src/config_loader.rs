@@ -1,9 +1,13 @@
-use crate::{layer::Layer, Config};
-use anyhow::{Context, Result};
+use crate::{
+    layer::{Layer, ReaderSpec},
+    CommandLayer, Config, Format, Source, UrlLayer,
+};
+use anyhow::{bail, Context, Result};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
+    io::Read,
     path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -26,6 +30,13 @@ struct LoaderSpec<T> {
     layers: Vec<Layer>,
     factory: Option<Arc<dyn Fn() -> T + 'static + Send + Sync>>,
     error_callbacks: Vec<Arc<ErrorCallback>>,
+    profile: Option<ProfileSelection>,
+}
+
+#[derive(Clone)]
+enum ProfileSelection {
+    Name(String),
+    EnvVar(String),
 }
 
 // we have to implement this ourselves without derive, because deriving adds constraint `where T: Clone`
@@ -35,6 +46,7 @@ impl<T> Clone for LoaderSpec<T> {
             layers: self.layers.clone(),
             factory: self.factory.clone(),
             error_callbacks: self.error_callbacks.clone(),
+            profile: self.profile.clone(),
         }
     }
 }
@@ -45,6 +57,7 @@ impl<T> Default for LoaderSpec<T> {
             layers: Default::default(),
             factory: None,
             error_callbacks: Default::default(),
+            profile: None,
         }
     }
 }
@@ -73,16 +86,208 @@ where
         self
     }
 
+    /// Selects a profile from the `profiles` sections of the loaded layers. Each layer's profile
+    /// is merged over that layer's own top-level keys, so later layers still take precedence.
+    /// Once profiles are enabled through this method or `with_profile_from_env`, the `profiles`
+    /// sections are removed before building the configuration
+    pub fn with_profile(mut self, name: impl Into<String>) -> Self {
+        self.spec
+            .profile
+            .replace(ProfileSelection::Name(name.into()));
+        self
+    }
+
+    /// Selects the profile named by an environment variable, like `with_profile` does. The
+    /// variable is read once when loading, and no profile is selected if it is unset or empty
+    pub fn with_profile_from_env(mut self, var: impl Into<String>) -> Self {
+        self.spec
+            .profile
+            .replace(ProfileSelection::EnvVar(var.into()));
+        self
+    }
+
     pub fn and_overlay_json(self, path: impl Into<PathBuf>) -> Self {
         self.and_overlay(Layer::json_file(path))
     }
 
+    /// Overlays a JSON5 file, which unlike plain JSON may contain comments, trailing commas
+    /// and unquoted keys
+    pub fn and_overlay_json5(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::json5_file(path))
+    }
+
     pub fn and_overlay_yaml(self, path: impl Into<PathBuf>) -> Self {
         self.and_overlay(Layer::yaml_file(path))
     }
 
+    pub fn and_overlay_toml(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::toml_file(path))
+    }
+
+    /// Overlays an INI file, with sections becoming nested objects. Unquoted values are coerced
+    /// from strings like environment variables are
+    pub fn and_overlay_ini(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::ini_file(path))
+    }
+
+    /// Overlays a Java properties file, with dotted keys becoming nested objects. Values are
+    /// coerced from strings like environment variables are
+    pub fn and_overlay_properties(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::properties_file(path))
+    }
+
+    /// Overlays a file whose format is chosen by its extension. Files with unknown extensions
+    /// are parsed as JSON if possible, and as YAML otherwise
+    pub fn and_overlay_file(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::auto_file(path))
+    }
+
+    /// Overlays a JSON file, nesting its contents under the dotted path `at`
+    pub fn and_overlay_json_at(self, at: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::mounted(at, Layer::json_file(path)))
+    }
+
+    /// Overlays a YAML file, nesting its contents under the dotted path `at`
+    pub fn and_overlay_yaml_at(self, at: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::mounted(at, Layer::yaml_file(path)))
+    }
+
+    /// Overlays a TOML file, nesting its contents under the dotted path `at`
+    pub fn and_overlay_toml_at(self, at: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::mounted(at, Layer::toml_file(path)))
+    }
+
+    /// Overlays a file of any supported format, nesting its contents under the dotted path `at`
+    pub fn and_overlay_file_at(self, at: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::mounted(at, Layer::auto_file(path)))
+    }
+
+    pub fn and_optional_overlay_json(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::optional(Layer::json_file(path)))
+    }
+
+    pub fn and_optional_overlay_yaml(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::optional(Layer::yaml_file(path)))
+    }
+
+    pub fn and_optional_overlay_toml(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::optional(Layer::toml_file(path)))
+    }
+
+    /// Overlays every file with a supported extension (such as `*.json`, `*.yaml` or `*.yml`)
+    /// in a directory, in lexicographic order. The directory is rescanned on every load, so
+    /// watching picks up added, removed and renamed files
+    pub fn and_overlay_dir(self, path: impl Into<PathBuf>) -> Self {
+        self.and_overlay(Layer::Dir(path.into()))
+    }
+
+    /// Overlays every file matching a glob pattern, in sorted order. The pattern is expanded
+    /// again on every load, so watching picks up files that start or stop matching
+    pub fn and_overlay_glob(self, pattern: impl Into<String>) -> Self {
+        self.and_overlay(Layer::Glob(pattern.into()))
+    }
+
     pub fn and_json_url(self, url: Url) -> Self {
-        self.and_overlay(Layer::JsonUrl(url))
+        self.and_url(url, Format::Json)
+    }
+
+    pub fn and_yaml_url(self, url: Url) -> Self {
+        self.and_url(url, Format::Yaml)
+    }
+
+    /// Overlays a document fetched from `url`, requesting it in the given format. Responses
+    /// whose Content-Type names another supported format are parsed as that format instead
+    pub fn and_url(self, url: Url, format: Format) -> Self {
+        self.and_url_layer(UrlLayer::new(url).format(format))
+    }
+
+    /// Overlays a document fetched from `url` in whichever supported format the server
+    /// responds with, as stated by its Content-Type or detected from the body
+    pub fn and_negotiated_url(self, url: Url) -> Self {
+        self.and_url_layer(UrlLayer::new(url))
+    }
+
+    pub fn and_url_layer(self, layer: UrlLayer) -> Self {
+        self.and_overlay(Layer::Url(layer))
+    }
+
+    /// Overlays environment variables named `{prefix}{separator}KEY{separator}SUBKEY...`,
+    /// mapping them to lowercased nested keys. Values are coerced from strings: `true`/`false`
+    /// become booleans, numbers become numbers, JSON arrays, objects and quoted strings are
+    /// parsed as JSON, and anything else stays a string. An empty prefix maps every variable,
+    /// named `KEY{separator}SUBKEY...`, while an empty separator fails the load
+    pub fn and_env(self, prefix: impl Into<String>, separator: impl Into<String>) -> Self {
+        self.and_overlay(Layer::Env {
+            prefix: prefix.into(),
+            separator: separator.into(),
+        })
+    }
+
+    /// Overlays the variables of a `.env` file, mapping their names to nested keys like
+    /// `and_env` does. Unquoted values are coerced the same way, while quoted values are always
+    /// kept as strings
+    pub fn and_overlay_dotenv(
+        self,
+        path: impl Into<PathBuf>,
+        prefix: impl Into<String>,
+        separator: impl Into<String>,
+    ) -> Self {
+        self.and_overlay(Layer::DotEnv {
+            path: path.into(),
+            prefix: prefix.into(),
+            separator: separator.into(),
+        })
+    }
+
+    /// Overlays the `--set key.path=value` (or `--set=key.path=value`) assignments found in
+    /// command-line arguments, such as `std::env::args()`. Other arguments are ignored. Values
+    /// are parsed as YAML scalars, and when the same key is assigned more than once the last
+    /// assignment wins. Assigning `null` (or `~`) removes the key
+    pub fn and_overrides<I, S>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.and_overlay(Layer::Overrides(args.into_iter().map(Into::into).collect()))
+    }
+
+    /// Overlays bare `key.path=value` assignments, like `and_overrides` does. Unlike it, every
+    /// item must be an assignment, and anything else fails the load
+    pub fn and_assignments<I, S>(self, assignments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.and_overlay(Layer::Assignments(
+            assignments.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    pub fn and_overlay_value(self, value: Value) -> Self {
+        self.and_overlay(Layer::Value(value))
+    }
+
+    /// Overlays a document compiled into the binary, typically through `include_str!`
+    pub fn and_overlay_str(self, format: Format, contents: &'static str) -> Self {
+        self.and_overlay(Layer::Str(format, contents))
+    }
+
+    pub fn and_command(self, command: CommandLayer) -> Self {
+        self.and_overlay(Layer::Command(command))
+    }
+
+    /// Overlays a document read from standard input. It is read once, on the first load, and
+    /// the watcher keeps using that document rather than reading standard input again
+    pub fn and_stdin(self, format: Format) -> Self {
+        self.and_reader(format, std::io::stdin())
+    }
+
+    pub(crate) fn and_reader(self, format: Format, reader: impl Read + Send + 'static) -> Self {
+        self.and_overlay(Layer::Reader(ReaderSpec::new(format, reader)))
+    }
+
+    pub fn and_source(self, source: impl Source + 'static) -> Self {
+        self.and_overlay(Layer::Source(Arc::new(source)))
     }
 
     fn and_overlay(mut self, layer: Layer) -> Self {
@@ -91,22 +296,59 @@ where
     }
 
     pub fn load(&self) -> Result<Config<T>> {
-        let returned = Config::new_with_raw(self.load_value()?)?;
+        let profile = self.selected_profile()?;
+        let returned = Config::new_with_raw(self.load_value(profile.as_deref())?, profile)?;
 
         Ok(returned)
     }
 
-    fn load_value(&self) -> Result<Value> {
+    fn selected_profile(&self) -> Result<Option<String>> {
+        match &self.spec.profile {
+            None => Ok(None),
+            Some(ProfileSelection::Name(name)) => Ok(Some(name.clone())),
+            Some(ProfileSelection::EnvVar(var)) => match std::env::var(var) {
+                Ok(name) if name.is_empty() => Ok(None),
+                Ok(name) => Ok(Some(name)),
+                Err(std::env::VarError::NotPresent) => Ok(None),
+                Err(e) => {
+                    Err(e).with_context(|| format!("Failed reading the profile from {var:?}"))
+                }
+            },
+        }
+    }
+
+    fn load_value(&self, profile: Option<&str>) -> Result<Value> {
         let mut value = match &self.spec.factory {
-            Some(factory) => serde_json::to_value(&factory())?,
+            Some(factory) => serde_json::to_value(factory())?,
             None => serde_json::Value::Null,
         };
 
+        let mut profile_found = false;
         for overlay_layer in &self.spec.layers {
-            let overlay_value = overlay_layer.load()?;
-
-            json_patch::merge(&mut value, &overlay_value);
+            for mut overlay_value in overlay_layer.load()? {
+                // `profiles` sections are left alone unless profiles are enabled on the loader
+                let mut selected = match &self.spec.profile {
+                    Some(_) => crate::profiles::take_profile(&mut overlay_value, profile)?,
+                    None => None,
+                };
+                if overlay_layer.is_file_based() {
+                    crate::file_values::read_file_values(&mut overlay_value)?;
+                    if let Some(selected) = &mut selected {
+                        crate::file_values::read_file_values(selected)?;
+                    }
+                }
+                json_patch::merge(&mut value, &overlay_value);
+                if let Some(selected) = selected {
+                    json_patch::merge(&mut value, &selected);
+                    profile_found = true;
+                }
+            }
         }
+        if let (Some(profile), false) = (profile, profile_found) {
+            bail!("Profile {profile:?} is not defined in the configuration");
+        }
+
+        crate::interpolation::expand_env(&mut value)?;
 
         Ok(value)
     }
@@ -123,6 +365,8 @@ where
         Config<T>: Clone,
     {
         let spec = self.spec.clone();
+        // the watcher keeps the profile selected by the initial load
+        let profile = config.profile();
 
         let mut prev_value = None;
 
@@ -133,7 +377,7 @@ where
 
             while !dropped.load(Ordering::Relaxed) {
                 let _ = loader
-                    .load_value()
+                    .load_value(profile.as_deref())
                     .context("Failed loading configuration value")
                     .and_then(|value| {
                         if Some(&value) != prev_value.as_ref() {
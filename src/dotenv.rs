@@ -0,0 +1,78 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use crate::layer::coerce_str_value;
+
+// Parses `.env` contents into key/value pairs, in file order. Supported syntax:
+// - blank lines and lines starting with `#` are ignored, as is a leading `export `
+// - unquoted values are trimmed, may be followed by a ` # comment`, and are coerced like
+//   environment variables
+// - single-quoted values are taken literally, and double-quoted values support the `\n`, `\r`,
+//   `\t`, `\"` and `\\` escapes. Quoted values are always kept as strings
+pub(crate) fn parse(contents: &str) -> Result<Vec<(String, Value)>> {
+    let mut returned = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => (key.trim(), value.trim_start()),
+            _ => bail!("Line {line_number}: expected KEY=value"),
+        };
+        let value = match parse_value(value) {
+            Ok(value) => value,
+            Err(e) => bail!("Line {line_number}: {e}"),
+        };
+        returned.push((key.to_owned(), value));
+    }
+    Ok(returned)
+}
+
+fn parse_value(value: &str) -> Result<Value> {
+    let (parsed, rest) = if let Some(quoted) = value.strip_prefix('\'') {
+        match quoted.split_once('\'') {
+            Some((parsed, rest)) => (parsed.to_owned(), rest),
+            None => bail!("unterminated single-quoted value"),
+        }
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        parse_double_quoted(quoted)?
+    } else {
+        let unquoted = match value.find(" #") {
+            Some(comment_start) => &value[..comment_start],
+            None => value,
+        };
+        return Ok(coerce_str_value(unquoted.trim_end()));
+    };
+
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        bail!("unexpected characters after quoted value");
+    }
+    Ok(Value::String(parsed))
+}
+
+fn parse_double_quoted(quoted: &str) -> Result<(String, &str)> {
+    let mut parsed = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((parsed, &quoted[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => parsed.push('\n'),
+                Some((_, 'r')) => parsed.push('\r'),
+                Some((_, 't')) => parsed.push('\t'),
+                Some((_, escaped @ ('"' | '\\'))) => parsed.push(escaped),
+                Some((_, other)) => {
+                    parsed.push('\\');
+                    parsed.push(other);
+                }
+                None => break,
+            },
+            c => parsed.push(c),
+        }
+    }
+    bail!("unterminated double-quoted value")
+}
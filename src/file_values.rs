@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use anyhow::{format_err, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{interpolation::expand_env_str, references::escape_placeholders};
+
+// the key of single-key objects standing for the contents of a file
+pub(crate) const FILE_KEY: &str = "$file";
+
+// Expands environment variables in every `{"$file": "path"}` path of a document read from a
+// configuration file, and resolves relative paths against `base_dir`, the directory of that
+// file. Files are only read later, by `read_file_values`, so that those referenced by profiles
+// that are not selected don't need to exist
+pub(crate) fn anchor_file_paths(value: &mut Value, base_dir: &Path) -> Result<()> {
+    visit(value, &mut Vec::new(), &mut |file_path, key| {
+        let file_path = expand_env_str(file_path).with_context(|| {
+            format!("Failed expanding environment variables in the file path of key {key:?}")
+        })?;
+        let anchored = base_dir.join(file_path);
+        let anchored = anchored
+            .to_str()
+            .ok_or_else(|| format_err!("File path {anchored:?} is not valid UTF-8"))?;
+        Ok(json!({ FILE_KEY: anchored }))
+    })
+}
+
+// Replaces every `{"$file": "path"}` object in the tree with the contents of the file at `path`,
+// trimmed of surrounding whitespace. This lets secrets mounted as files, such as Docker and
+// Kubernetes secrets, stay out of the configuration files themselves. Contents are always kept
+// as literal strings. Only patches of file-based layers are read, so that other layers, such as
+// URLs or environment variables, can't make us read local files
+pub(crate) fn read_file_values(value: &mut Value) -> Result<()> {
+    visit(value, &mut Vec::new(), &mut |file_path, key| {
+        let contents = std::fs::read_to_string(file_path).with_context(|| {
+            format!("Failed reading file {file_path:?} referenced by key {key:?}")
+        })?;
+        Ok(Value::String(escape_placeholders(contents.trim())))
+    })
+}
+
+pub(crate) fn contains_file_values(value: &Value) -> bool {
+    file_reference(value).is_some()
+        || match value {
+            Value::Array(items) => items.iter().any(contains_file_values),
+            Value::Object(map) => map.values().any(contains_file_values),
+            _ => false,
+        }
+}
+
+// Replaces every `{"$file": "path"}` object with what `replace` returns for its path and key
+fn visit(
+    value: &mut Value,
+    path: &mut Vec<String>,
+    replace: &mut impl FnMut(&str, &str) -> Result<Value>,
+) -> Result<()> {
+    if let Some(file_path) = file_reference(value).map(str::to_owned) {
+        *value = replace(&file_path, &path.join("."))?;
+        return Ok(());
+    }
+
+    match value {
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push(index.to_string());
+                visit(item, path, replace)?;
+                path.pop();
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                path.push(key.clone());
+                visit(item, path, replace)?;
+                path.pop();
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn file_reference(value: &Value) -> Option<&str> {
+    match value.as_object() {
+        Some(map) if map.len() == 1 => map.get(FILE_KEY)?.as_str(),
+        _ => None,
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use crate::{config::nest_under, layer::coerce_str_value};
+
+// Parses INI contents into nested objects. Keys before the first section are top-level keys,
+// and `[a.b]` sections nest their keys under `a.b`. Lines starting with `;` or `#` are
+// comments, and so is the rest of a line after a ` ;` or ` #` following a value. Double- or
+// single-quoted values are kept as strings (without their quotes), and other values are coerced
+// like environment variables
+pub(crate) fn parse(contents: &str) -> Result<Value> {
+    let mut returned = Value::Object(Default::default());
+    let mut section: Vec<&str> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            match name.strip_suffix(']').map(str::trim) {
+                Some(name) if !name.is_empty() => {
+                    section = name.split('.').map(str::trim).collect()
+                }
+                _ => bail!("Line {line_number}: invalid section header"),
+            }
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => (key.trim(), value.trim()),
+            _ => bail!("Line {line_number}: expected key = value"),
+        };
+        let value = strip_inline_comment(value);
+        let value = match unquote(value) {
+            Some(unquoted) => Value::String(unquoted.to_owned()),
+            None => coerce_str_value(value),
+        };
+        let patch = nest_under(section.iter().copied().chain(Some(key)), value);
+        json_patch::merge(&mut returned, &patch);
+    }
+    Ok(returned)
+}
+
+// Comments must be preceded by whitespace, so that values like `http://host/#anchor` are kept
+// whole. Inside quoted values, comment characters are part of the value
+fn strip_inline_comment(value: &str) -> &str {
+    // the closing quote of a quoted value, past which comments may start
+    let search_from = ['"', '\''].iter().find_map(|quote| {
+        let rest = value.strip_prefix(*quote)?;
+        rest.find(*quote).map(|end| end + 2)
+    });
+    let search_from = search_from.unwrap_or(0);
+    let comment_start = value[search_from..]
+        .char_indices()
+        .map(|(index, c)| (search_from + index, c))
+        .find(|&(index, c)| {
+            matches!(c, ';' | '#') && value[..index].ends_with(char::is_whitespace)
+        });
+    match comment_start {
+        Some((index, _)) => value[..index].trim_end(),
+        None => value,
+    }
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    ['"', '\''].iter().find_map(|quote| {
+        value
+            .strip_prefix(*quote)
+            .and_then(|value| value.strip_suffix(*quote))
+    })
+}
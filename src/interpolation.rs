@@ -0,0 +1,128 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use crate::references::escape_placeholders;
+
+// Expands `${VAR}`, `${VAR:-default}` and `${VAR:?message}` placeholders in every string value
+// of the tree:
+// - `${VAR}` is left untouched when VAR is unset
+// - `${VAR:-default}` falls back to `default` when VAR is unset or empty
+// - `${VAR:?message}` fails with `message` when VAR is unset or empty
+// Only placeholders naming a valid environment variable are expanded, and others are left
+// untouched, as are placeholders escaped as `$${VAR}`. Escapes are kept here, and unescaped
+// once references are resolved. Expanded values are always kept as strings, and their own
+// placeholders are escaped so that they are never resolved
+pub(crate) fn expand_env(value: &mut Value) -> Result<()> {
+    expand_at(value, &mut Vec::new())
+}
+
+fn expand_at(value: &mut Value, path: &mut Vec<String>) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            if let Some(expanded) =
+                expand_str(s, true).map_err(|e| e.context(describe_key(path)))?
+            {
+                *value = Value::String(expanded);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push(index.to_string());
+                expand_at(item, path)?;
+                path.pop();
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                path.push(key.clone());
+                expand_at(item, path)?;
+                path.pop();
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn describe_key(path: &[String]) -> String {
+    format!(
+        "Failed expanding environment variables in key {:?}",
+        path.join(".")
+    )
+}
+
+// Expands the placeholders of a single string that is used as-is rather than kept in the tree,
+// such as a `$file` path, so expanded values are not escaped and escapes are unescaped
+pub(crate) fn expand_env_str(s: &str) -> Result<String> {
+    Ok(expand_str(s, false)?.unwrap_or_else(|| s.to_owned()))
+}
+
+// Returns `None` if the string is left unchanged. Escapes are kept, and
+// expanded values escaped, only when `keep_escapes` is set
+fn expand_str(s: &str, keep_escapes: bool) -> Result<Option<String>> {
+    let mut returned = String::new();
+    let mut rest = s;
+    let mut expanded_any = false;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            let escape_end = if keep_escapes { start } else { start - 1 };
+            returned.push_str(&rest[..escape_end]);
+            returned.push_str("${");
+            rest = &rest[start + 2..];
+            expanded_any |= !keep_escapes;
+            continue;
+        }
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let placeholder = &rest[start + 2..end];
+        returned.push_str(&rest[..start]);
+        match expand_placeholder(placeholder)? {
+            Some(expansion) => {
+                if keep_escapes {
+                    returned.push_str(&escape_placeholders(&expansion));
+                } else {
+                    returned.push_str(&expansion);
+                }
+                expanded_any = true;
+            }
+            None => returned.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    returned.push_str(rest);
+
+    Ok(expanded_any.then_some(returned))
+}
+
+// Returns `None` for placeholders to leave untouched
+fn expand_placeholder(placeholder: &str) -> Result<Option<String>> {
+    let (name, modifier) = match placeholder.find(':') {
+        Some(index) => (&placeholder[..index], Some(&placeholder[index..])),
+        None => (placeholder, None),
+    };
+    let valid_modifier = modifier.is_none_or(|m| m.starts_with(":-") || m.starts_with(":?"));
+    if !is_variable_name(name) || !valid_modifier {
+        return Ok(None);
+    }
+    let value = std::env::var(name).ok();
+    Ok(Some(match (value, modifier) {
+        (Some(value), None) => value,
+        (None, None) => return Ok(None),
+        (Some(value), Some(_)) if !value.is_empty() => value,
+        (_, Some(modifier)) => match modifier.split_at(2) {
+            (":-", default) => default.to_owned(),
+            (_, "") => bail!("Environment variable {name} is not set"),
+            (_, message) => bail!("Environment variable {name} is not set: {message}"),
+        },
+    }))
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
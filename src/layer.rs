@@ -1,16 +1,48 @@
 use std::{
+    io::Read,
     path::{Path, PathBuf},
-    time::Duration,
+    sync::Arc,
 };
 
-use anyhow::{Context, Result};
-use reqwest::Url;
+use anyhow::{bail, format_err, Context, Result};
+use parking_lot::Mutex;
+use serde::Deserialize;
 use serde_json::Value;
 
+use crate::{
+    compression::{uncompressed_path, Compression},
+    file_values::{anchor_file_paths, contains_file_values, FILE_KEY},
+    url_layer::UrlLayer,
+    CommandLayer, Source,
+};
+
 #[derive(Clone)]
 pub(crate) enum Layer {
     File(FileSpec),
-    JsonUrl(Url),
+    OptionalFile(FileSpec),
+    Dir(PathBuf),
+    Glob(String),
+    Url(UrlLayer),
+    Env {
+        prefix: String,
+        separator: String,
+    },
+    DotEnv {
+        path: PathBuf,
+        prefix: String,
+        separator: String,
+    },
+    Overrides(Vec<String>),
+    Assignments(Vec<String>),
+    Source(Arc<dyn Source>),
+    Command(CommandLayer),
+    Value(Value),
+    Str(Format, &'static str),
+    Reader(ReaderSpec),
+    Mounted {
+        at: String,
+        layer: Box<Layer>,
+    },
 }
 
 impl Layer {
@@ -18,46 +50,577 @@ impl Layer {
         Self::File(FileSpec::Json(path.into()))
     }
 
+    pub(crate) fn json5_file(path: impl Into<PathBuf>) -> Self {
+        Self::File(FileSpec::Json5(path.into()))
+    }
+
     pub(crate) fn yaml_file(path: impl Into<PathBuf>) -> Self {
         Self::File(FileSpec::Yaml(path.into()))
     }
 
-    pub(crate) fn load(&self) -> Result<Value> {
+    pub(crate) fn toml_file(path: impl Into<PathBuf>) -> Self {
+        Self::File(FileSpec::Toml(path.into()))
+    }
+
+    pub(crate) fn ini_file(path: impl Into<PathBuf>) -> Self {
+        Self::File(FileSpec::Ini(path.into()))
+    }
+
+    pub(crate) fn properties_file(path: impl Into<PathBuf>) -> Self {
+        Self::File(FileSpec::Properties(path.into()))
+    }
+
+    pub(crate) fn auto_file(path: impl Into<PathBuf>) -> Self {
+        Self::File(FileSpec::Auto(path.into()))
+    }
+
+    // Nests everything the layer contributes under a dotted path
+    pub(crate) fn mounted(at: impl Into<String>, layer: Self) -> Self {
+        Self::Mounted {
+            at: at.into(),
+            layer: Box::new(layer),
+        }
+    }
+
+    // Makes file layers tolerate their file being missing
+    pub(crate) fn optional(layer: Self) -> Self {
+        match layer {
+            Self::File(spec) => Self::OptionalFile(spec),
+            other => other,
+        }
+    }
+
+    // Returns the merge patches this layer contributes, in the order they should be applied
+    pub(crate) fn load(&self) -> Result<Vec<Value>> {
+        let returned = self.load_patches()?;
+        // includes and file values are relative to the declaring file, so other layers can't
+        // have them, and they would otherwise let any layer read local files
+        if !self.is_file_based() {
+            if returned
+                .iter()
+                .any(|patch| patch.get(INCLUDE_KEY).is_some())
+            {
+                bail!("{INCLUDE_KEY:?} is only supported in configuration files");
+            }
+            if returned.iter().any(contains_file_values) {
+                bail!("{FILE_KEY:?} is only supported in configuration files");
+            }
+        }
+        Ok(returned)
+    }
+
+    pub(crate) fn is_file_based(&self) -> bool {
+        match self {
+            Self::File(_) | Self::OptionalFile(_) | Self::Dir(_) | Self::Glob(_) => true,
+            Self::Mounted { layer, .. } => layer.is_file_based(),
+            _ => false,
+        }
+    }
+
+    fn load_patches(&self) -> Result<Vec<Value>> {
         Ok(match self {
-            Self::File(spec) => {
-                let path = spec.path();
-                let file = std::fs::File::open(spec.path())?;
-                match &spec {
-                    FileSpec::Json(_) => serde_json::from_reader(file).map_err(anyhow::Error::from),
-                    FileSpec::Yaml(_) => serde_yaml::from_reader(file).map_err(anyhow::Error::from),
+            Self::File(spec) => spec.load()?,
+            Self::OptionalFile(spec) => spec.load_if_exists()?,
+            Self::Dir(path) => load_all(
+                dir_file_specs(path)
+                    .with_context(|| format!("Failed listing configuration directory {path:?}"))?,
+            )?,
+            Self::Glob(pattern) => load_all(
+                glob_file_specs(pattern)
+                    .with_context(|| format!("Failed expanding configuration glob {pattern:?}"))?,
+            )?,
+            Self::Url(layer) => layer
+                .load()
+                .with_context(|| format!("Failed reading configuration from {}", layer.url()))?,
+            Self::Env { prefix, separator } => {
+                // variables whose name or value isn't valid UTF-8 are skipped
+                let mut vars: Vec<_> = std::env::vars_os()
+                    .filter_map(|(key, value)| {
+                        Some((key.into_string().ok()?, value.into_string().ok()?))
+                    })
+                    .collect();
+                vars.sort();
+                let vars = vars
+                    .into_iter()
+                    .map(|(key, value)| (key, coerce_str_value(&value)));
+                vec![nested_from_pairs(vars, prefix, separator)?]
+            }
+            Self::DotEnv {
+                path,
+                prefix,
+                separator,
+            } => {
+                let pairs = std::fs::read_to_string(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|contents| crate::dotenv::parse(&contents))
+                    .with_context(|| format!("Failed loading dotenv file {path:?}"))?;
+                vec![nested_from_pairs(pairs, prefix, separator)?]
+            }
+            Self::Source(source) => vec![source.load().with_context(|| {
+                format!("Failed loading configuration from {}", source.describe())
+            })?],
+            Self::Command(command) => command.load().with_context(|| {
+                format!(
+                    "Failed running configuration command {}",
+                    command.describe()
+                )
+            })?,
+            Self::Value(value) => vec![value.clone()],
+            Self::Str(format, contents) => format
+                .parse(contents)
+                .context("Failed parsing embedded configuration")?,
+            Self::Reader(spec) => spec.load()?,
+            Self::Mounted { at, layer } => layer
+                .load()?
+                .into_iter()
+                .map(|patch| crate::config::nest_under(at.split('.'), patch))
+                .collect(),
+            Self::Overrides(args) => {
+                parse_overrides(args).context("Failed parsing configuration overrides")?
+            }
+            Self::Assignments(assignments) => {
+                parse_assignments(assignments.iter().map(String::as_str))
+                    .context("Failed parsing configuration assignments")?
+            }
+        })
+    }
+}
+
+// A document read once, on first load, and reused afterwards. Clones share the state, so that
+// the watcher doesn't try reading the stream again
+#[derive(Clone)]
+pub(crate) struct ReaderSpec {
+    format: Format,
+    state: Arc<Mutex<ReaderState>>,
+}
+
+enum ReaderState {
+    Pending(Box<dyn Read + Send>),
+    Loaded(Vec<Value>),
+    // the stream is consumed even if its contents are invalid, so the error has to be kept
+    Failed(String),
+}
+
+impl ReaderSpec {
+    pub(crate) fn new(format: Format, reader: impl Read + Send + 'static) -> Self {
+        Self {
+            format,
+            state: Arc::new(Mutex::new(ReaderState::Pending(Box::new(reader)))),
+        }
+    }
+
+    fn load(&self) -> Result<Vec<Value>> {
+        let mut state = self.state.lock();
+        if let ReaderState::Pending(reader) = &mut *state {
+            let mut contents = String::new();
+            *state = match reader
+                .read_to_string(&mut contents)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| self.format.parse(&contents))
+            {
+                Ok(documents) => ReaderState::Loaded(documents),
+                Err(e) => ReaderState::Failed(format!("{e:#}")),
+            };
+        }
+        match &*state {
+            ReaderState::Loaded(documents) => Ok(documents.clone()),
+            ReaderState::Failed(message) => bail!("Failed reading configuration stream: {message}"),
+            ReaderState::Pending(_) => unreachable!("stream was just read"),
+        }
+    }
+}
+
+// Lists the configuration files in a directory, sorted by name. Files with unrecognized
+// extensions and hidden entries (such as editor swap files or the `..data` directory of
+// Kubernetes volume mounts) are skipped
+fn dir_file_specs(path: &Path) -> Result<Vec<FileSpec>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        let hidden = entry_path
+            .file_name()
+            .is_none_or(|name| name.to_string_lossy().starts_with('.'));
+        if !hidden && entry_path.is_file() {
+            paths.push(entry_path);
+        }
+    }
+    paths.sort();
+    Ok(paths
+        .into_iter()
+        .filter(|path| Format::from_path(path).is_some())
+        .map(FileSpec::Auto)
+        .collect())
+}
+
+// Lists the files matching a glob pattern, sorted by path. Their format is chosen by extension,
+// falling back to sniffing the contents
+fn glob_file_specs(pattern: &str) -> Result<Vec<FileSpec>> {
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths.into_iter().map(FileSpec::Auto).collect())
+}
+
+fn load_all(specs: Vec<FileSpec>) -> Result<Vec<Value>> {
+    let mut returned = Vec::new();
+    for spec in &specs {
+        returned.extend(spec.load()?);
+    }
+    Ok(returned)
+}
+
+// Picks the `--set key=value` and `--set=key=value` assignments out of command-line arguments,
+// ignoring every other argument
+fn parse_overrides(args: &[String]) -> Result<Vec<Value>> {
+    let mut assignments = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--set" {
+            assignments.push(
+                args.next()
+                    .ok_or_else(|| format_err!("Missing key=value after --set"))?
+                    .as_str(),
+            );
+        } else if let Some(assignment) = arg.strip_prefix("--set=") {
+            assignments.push(assignment);
+        }
+    }
+    parse_assignments(assignments)
+}
+
+// Each `key=value` assignment is a patch of its own, applied in order, so the last assignment of
+// a key wins and assigning `null` (or `~`) removes the key
+fn parse_assignments<'a>(assignments: impl IntoIterator<Item = &'a str>) -> Result<Vec<Value>> {
+    let mut returned = Vec::new();
+    for assignment in assignments {
+        let (key, value) = assignment
+            .split_once('=')
+            .filter(|(key, _)| !key.is_empty())
+            .ok_or_else(|| format_err!("Invalid override {assignment:?}, expected key=value"))?;
+
+        returned.push(crate::config::nest_under(
+            key.split('.'),
+            parse_yaml_scalar(value),
+        ));
+    }
+    Ok(returned)
+}
+
+// Values which are not YAML scalars (or not valid YAML at all) are kept as strings
+fn parse_yaml_scalar(s: &str) -> Value {
+    match serde_yaml::from_str(s) {
+        Ok(value @ (Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_)))
+            if !s.trim().is_empty() =>
+        {
+            value
+        }
+        _ => Value::String(s.to_owned()),
+    }
+}
+
+// Maps `{prefix}{separator}A{separator}B` keys into `{"a": {"b": value}}`, or `A{separator}B`
+// keys when the prefix is empty. Pairs not matching the prefix are ignored, and later pairs win
+// over earlier ones. The separator must not be empty
+fn nested_from_pairs(
+    pairs: impl IntoIterator<Item = (String, Value)>,
+    prefix: &str,
+    separator: &str,
+) -> Result<Value> {
+    if separator.is_empty() {
+        bail!("The separator of nested keys must not be empty");
+    }
+    let full_prefix = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{prefix}{separator}")
+    };
+    let mut returned = Value::Object(Default::default());
+    for (key, value) in pairs {
+        let key = match key.strip_prefix(&full_prefix) {
+            Some(key) if !key.is_empty() => key.to_lowercase(),
+            _ => continue,
+        };
+        let patch = crate::config::nest_under(key.split(separator), value);
+        json_patch::merge(&mut returned, &patch);
+    }
+    Ok(returned)
+}
+
+// String-only sources (environment variables and the like) get their values coerced as follows:
+// `true`/`false` become booleans, anything parsing as a JSON number becomes a number, and
+// well-formed JSON arrays, objects and quoted strings are taken as-is. Everything else,
+// including `null`, is kept as a plain string
+pub(crate) fn coerce_str_value(s: &str) -> Value {
+    let trimmed = s.trim();
+    if trimmed.starts_with(['[', '{', '"']) || trimmed.parse::<f64>().is_ok() {
+        if let Ok(
+            value @ (Value::Array(_) | Value::Object(_) | Value::String(_) | Value::Number(_)),
+        ) = serde_json::from_str(trimmed)
+        {
+            return value;
+        }
+    }
+    match trimmed {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(s.to_owned()),
+    }
+}
+
+/// A configuration document format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    // JSON with comments, trailing commas, unquoted keys and the other JSON5 extensions
+    Json5,
+    Yaml,
+    Toml,
+    Ini,
+    Properties,
+}
+
+impl Format {
+    // Compressed files are recognized by the extension preceding the compression one, as in
+    // `config.yaml.gz`
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match uncompressed_path(path).extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "json5" => Some(Self::Json5),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "ini" => Some(Self::Ini),
+            "properties" => Some(Self::Properties),
+            _ => None,
+        }
+    }
+
+    // Accepts both the registered media types and the common `x-`/`text/` variants
+    pub(crate) fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/json" | "text/json" => Some(Self::Json),
+            "application/json5" => Some(Self::Json5),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                Some(Self::Yaml)
+            }
+            "application/toml" | "text/toml" | "text/x-toml" => Some(Self::Toml),
+            _ if mime.ends_with("+json") => Some(Self::Json),
+            _ if mime.ends_with("+yaml") => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn media_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Json5 => "application/json5",
+            Self::Yaml => "application/yaml",
+            Self::Toml => "application/toml",
+            // neither format has a registered media type
+            Self::Ini | Self::Properties => "text/plain",
+        }
+    }
+
+    // Returns one merge patch per document. Only YAML supports several `---`-separated
+    // documents in a single stream, and empty YAML documents contribute nothing
+    pub(crate) fn parse(self, contents: &str) -> Result<Vec<Value>> {
+        Ok(match self {
+            Self::Json => vec![serde_json::from_str(contents)?],
+            Self::Json5 => vec![json5::from_str(contents).map_err(|e| match e {
+                json5::Error::Message {
+                    msg,
+                    location: Some(location),
+                } => format_err!("{msg} at line {} column {}", location.line, location.column),
+                e => e.into(),
+            })?],
+            Self::Yaml => {
+                let mut documents = Vec::new();
+                for document in serde_yaml::Deserializer::from_str(contents) {
+                    // an invalid document ends the stream, so we have to stop at the first error
+                    let value = Value::deserialize(document)?;
+                    if !value.is_null() {
+                        documents.push(value);
+                    }
                 }
-                .with_context(|| format!("Failed loading configuration overlay from {path:?}"))?
-            }
-            Self::JsonUrl(url) => {
-                let client = reqwest::blocking::Client::builder()
-                    .timeout(Duration::from_secs(5))
-                    .build()?;
-                client
-                    .get(url.to_owned())
-                    .send()
-                    .and_then(|resp| resp.json())
-                    .with_context(|| format!("Failed reading configuration from {url}"))?
+                documents
             }
+            Self::Toml => vec![toml_to_json(toml::from_str(contents)?)],
+            Self::Ini => vec![crate::ini::parse(contents)?],
+            Self::Properties => vec![crate::properties::parse(contents)?],
         })
     }
+
+    // Used when the format isn't known up front. JSON is tried first since YAML would accept
+    // most JSON documents as well, but report errors in YAML terms
+    pub(crate) fn sniff_and_parse(contents: &str) -> Result<Vec<Value>> {
+        Self::Json
+            .parse(contents)
+            .or_else(|_| Self::Yaml.parse(contents))
+            .context("Content is neither valid JSON nor valid YAML")
+    }
 }
 
 #[derive(Clone)]
 pub(crate) enum FileSpec {
     Json(PathBuf),
+    Json5(PathBuf),
     Yaml(PathBuf),
+    Toml(PathBuf),
+    Ini(PathBuf),
+    Properties(PathBuf),
+    // format is chosen by the file extension, falling back to sniffing the contents
+    Auto(PathBuf),
 }
 
+// Files may name other files to be merged underneath them through this key, either as a single
+// path or as a list of paths, relative to the including file
+const INCLUDE_KEY: &str = "$include";
+
 impl FileSpec {
+    fn load(&self) -> Result<Vec<Value>> {
+        let path = self.path();
+        let file = std::fs::File::open(path)?;
+        self.parse_with_includes(file, &mut Vec::new())
+            .with_context(|| format!("Failed loading configuration overlay from {path:?}"))
+    }
+
+    // Missing files contribute nothing rather than failing
+    fn load_if_exists(&self) -> Result<Vec<Value>> {
+        let path = self.path();
+        let file = match std::fs::File::open(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            file => file?,
+        };
+        self.parse_with_includes(file, &mut Vec::new())
+            .with_context(|| format!("Failed loading configuration overlay from {path:?}"))
+    }
+
+    // `including` holds the canonical paths of the files currently being included, innermost
+    // last, so that include cycles can be detected
+    fn parse_with_includes(
+        &self,
+        file: std::fs::File,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<Vec<Value>> {
+        let canonical_path = self.path().canonicalize()?;
+        if including.contains(&canonical_path) {
+            let cycle = including
+                .iter()
+                .chain(Some(&canonical_path))
+                .map(|p| format!("{p:?}"))
+                .collect::<Vec<_>>();
+            bail!("Include cycle detected: {}", cycle.join(" -> "));
+        }
+
+        let mut documents = self.parse(file)?;
+        let base_dir = self.path().parent().unwrap_or_else(|| Path::new(""));
+        for document in &mut documents {
+            anchor_file_paths(document, base_dir)?;
+        }
+        including.push(canonical_path);
+        let returned = documents
+            .into_iter()
+            .map(|document| self.resolve_includes(document, including))
+            .collect::<Result<Vec<_>>>();
+        including.pop();
+
+        Ok(returned?.into_iter().flatten().collect())
+    }
+
+    // Included documents come before the including one, so that its own values take precedence
+    fn resolve_includes(
+        &self,
+        mut document: Value,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<Vec<Value>> {
+        let included = match document
+            .as_object_mut()
+            .and_then(|map| map.remove(INCLUDE_KEY))
+        {
+            None => return Ok(vec![document]),
+            Some(Value::String(path)) => vec![path],
+            Some(Value::Array(paths)) => paths
+                .into_iter()
+                .map(|path| match path {
+                    Value::String(path) => Ok(path),
+                    other => bail!("Included paths must be strings, found {other}"),
+                })
+                .collect::<Result<_>>()?,
+            Some(other) => {
+                bail!("{INCLUDE_KEY:?} must be a path or a list of paths, found {other}")
+            }
+        };
+
+        let base_dir = self.path().parent().unwrap_or_else(|| Path::new(""));
+        let mut returned = Vec::new();
+        for included_path in included {
+            let spec = FileSpec::Auto(base_dir.join(included_path));
+            let path = spec.path();
+            let included_documents = std::fs::File::open(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| spec.parse_with_includes(file, including))
+                .with_context(|| format!("Failed loading included file {path:?}"))?;
+            returned.extend(included_documents);
+        }
+        returned.push(document);
+        Ok(returned)
+    }
+
+    // `.gz` and `.zst` files are decompressed before being parsed
+    fn parse(&self, mut file: std::fs::File) -> Result<Vec<Value>> {
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        if let Some(compression) = Compression::from_path(self.path()) {
+            contents = compression.decompress(&contents)?;
+        }
+        let contents = String::from_utf8(contents)?;
+        match self.format() {
+            Some(format) => format.parse(&contents),
+            None => Format::sniff_and_parse(&contents),
+        }
+    }
+
+    fn format(&self) -> Option<Format> {
+        match self {
+            FileSpec::Json(_) => Some(Format::Json),
+            FileSpec::Json5(_) => Some(Format::Json5),
+            FileSpec::Yaml(_) => Some(Format::Yaml),
+            FileSpec::Toml(_) => Some(Format::Toml),
+            FileSpec::Ini(_) => Some(Format::Ini),
+            FileSpec::Properties(_) => Some(Format::Properties),
+            FileSpec::Auto(p) => Format::from_path(p),
+        }
+    }
+
     fn path(&self) -> &Path {
         match self {
             FileSpec::Json(p) => p,
+            FileSpec::Json5(p) => p,
             FileSpec::Yaml(p) => p,
+            FileSpec::Toml(p) => p,
+            FileSpec::Ini(p) => p,
+            FileSpec::Properties(p) => p,
+            FileSpec::Auto(p) => p,
         }
     }
 }
+
+// TOML datetimes have no JSON counterpart, so we keep them as their RFC 3339 strings
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => i.into(),
+        toml::Value::Float(f) => serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(f.to_string())),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => a.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(t) => t.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect(),
+    }
+}
@@ -1,10 +1,21 @@
 #![deny(warnings)]
 #![deny(clippy::dbg_macro, clippy::todo, clippy::unimplemented)]
+mod command_layer;
+mod compression;
 mod config;
 mod config_loader;
+mod dotenv;
+mod file_values;
+mod ini;
+mod interpolation;
 mod layer;
+mod profiles;
+mod properties;
+mod references;
+mod source;
 #[cfg(test)]
 mod tests;
+mod url_layer;
 
 #[cfg(doctest)]
 mod test_readme {
@@ -18,4 +29,8 @@ mod test_readme {
     external_doc_test!(include_str!("../README.md"));
 }
 
+pub use command_layer::CommandLayer;
 pub use config::Config;
+pub use layer::Format;
+pub use source::Source;
+pub use url_layer::UrlLayer;
@@ -0,0 +1,29 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+// the top-level key holding the profiles, by name
+const PROFILES_KEY: &str = "profiles";
+
+// Removes the `profiles` section from a single patch, returning the selected profile if the
+// section defines it. The profile is meant to be merged right after the patch itself, so that
+// it overrides the patch's own keys while later patches still take precedence over both
+pub(crate) fn take_profile(patch: &mut Value, profile: Option<&str>) -> Result<Option<Value>> {
+    let profiles = match patch
+        .as_object_mut()
+        .and_then(|map| map.remove(PROFILES_KEY))
+    {
+        Some(Value::Object(profiles)) => profiles,
+        Some(other) => bail!("{PROFILES_KEY:?} must be an object of profiles, found {other}"),
+        None => return Ok(None),
+    };
+
+    let profile = match profile {
+        Some(profile) => profile,
+        None => return Ok(None),
+    };
+    match profiles.get(profile) {
+        Some(selected @ Value::Object(_)) => Ok(Some(selected.clone())),
+        Some(other) => bail!("Profile {profile:?} must be an object, found {other}"),
+        None => Ok(None),
+    }
+}
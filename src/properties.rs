@@ -0,0 +1,89 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use crate::{config::nest_under, layer::coerce_str_value};
+
+// Parses Java `.properties` contents, nesting dotted keys into objects. Follows the format of
+// `java.util.Properties`: `#` and `!` comments, `=`, `:` or whitespace separators, trailing
+// backslash line continuations and backslash escapes (including `\uXXXX`). Values are coerced
+// like environment variables, and when a key is repeated the last value wins
+pub(crate) fn parse(contents: &str) -> Result<Value> {
+    let mut returned = Value::Object(Default::default());
+    let mut lines = contents.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let mut logical_line = line.trim_start().to_owned();
+        if logical_line.is_empty() || logical_line.starts_with(['#', '!']) {
+            continue;
+        }
+        while ends_with_continuation(&logical_line) {
+            logical_line.pop();
+            match lines.next() {
+                Some((_, next)) => logical_line.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+
+        let (key, value) = match split_key_value(&logical_line) {
+            Ok(pair) => pair,
+            Err(e) => bail!("Line {line_number}: {e}"),
+        };
+        let patch = nest_under(key.split('.'), coerce_str_value(&value));
+        json_patch::merge(&mut returned, &patch);
+    }
+    Ok(returned)
+}
+
+// a line continues if it ends with an odd number of backslashes
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+fn split_key_value(line: &str) -> Result<(String, String)> {
+    let mut chars = line.chars().peekable();
+    let mut key = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => key.push(unescape(&mut chars)?),
+            '=' | ':' => break,
+            c if c.is_whitespace() => {
+                // whitespace may be followed by an actual separator
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                chars.next_if(|c| *c == '=' || *c == ':');
+                break;
+            }
+            c => key.push(c),
+        }
+    }
+    if key.is_empty() {
+        bail!("missing key");
+    }
+
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(unescape(&mut chars)?),
+            c => value.push(c),
+        }
+    }
+    Ok((key, value))
+}
+
+fn unescape(chars: &mut impl Iterator<Item = char>) -> Result<char> {
+    Ok(match chars.next() {
+        Some('t') => '\t',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('f') => '\u{c}',
+        Some('u') => {
+            let hex: String = chars.take(4).collect();
+            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                Some(c) if hex.len() == 4 => c,
+                _ => bail!("invalid unicode escape \\u{hex}"),
+            }
+        }
+        Some(other) => other,
+        None => '\\',
+    })
+}
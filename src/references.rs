@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+// Resolves `${.a.b}` references to other keys of the tree, the leading dot standing for its
+// root, so that `${.port}` refers to the top-level `port` key. A string consisting of a single
+// reference takes the referenced value as-is, whatever its type. References embedded in a
+// longer string must point at strings, numbers or booleans. Other placeholders are left
+// untouched, and array elements are addressed by their index, as in `${.servers.0.host}`.
+// Placeholders escaped as `$${...}` are unescaped into literal `${...}` text
+pub(crate) fn resolve_references(root: &Value) -> Result<Value> {
+    Resolver {
+        root,
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+    }
+    .resolve_at(&[])
+}
+
+struct Resolver<'a> {
+    root: &'a Value,
+    resolved: HashMap<Vec<String>, Value>,
+    // the keys currently being resolved, used to detect reference cycles
+    stack: Vec<Vec<String>>,
+}
+
+impl Resolver<'_> {
+    fn resolve_at(&mut self, path: &[String]) -> Result<Value> {
+        if let Some(resolved) = self.resolved.get(path) {
+            return Ok(resolved.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|p| p == path) {
+            let cycle = self.stack[start..]
+                .iter()
+                .chain(Some(&path.to_vec()))
+                .map(|p| p.join("."))
+                .collect::<Vec<_>>();
+            bail!("Reference cycle detected: {}", cycle.join(" -> "));
+        }
+
+        let value = lookup(self.root, path).ok_or_else(|| {
+            anyhow::format_err!(
+                "Key {:?} referenced by {:?} does not exist",
+                path.join("."),
+                self.stack.last().map(|p| p.join(".")).unwrap_or_default()
+            )
+        })?;
+        self.stack.push(path.to_vec());
+        let resolved = match value {
+            Value::String(s) => self.resolve_str(s, path),
+            Value::Array(items) => (0..items.len())
+                .map(|index| self.resolve_at(&child_path(path, index.to_string())))
+                .collect::<Result<_>>()
+                .map(Value::Array),
+            Value::Object(map) => map
+                .keys()
+                .map(|key| {
+                    Ok((
+                        key.clone(),
+                        self.resolve_at(&child_path(path, key.clone()))?,
+                    ))
+                })
+                .collect::<Result<_>>()
+                .map(Value::Object),
+            other => Ok(other.clone()),
+        };
+        self.stack.pop();
+        let resolved = resolved?;
+
+        self.resolved.insert(path.to_vec(), resolved.clone());
+        Ok(resolved)
+    }
+
+    fn resolve_str(&mut self, s: &str, path: &[String]) -> Result<Value> {
+        let mut returned = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            // escaped placeholders are kept as literal text, without the escaping `$`
+            if rest[..start].ends_with('$') {
+                returned.push_str(&rest[..start - 1]);
+                returned.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let name = match rest[start + 2..end].strip_prefix('.') {
+                Some(name) if is_reference(name) => name,
+                _ => {
+                    returned.push_str(&rest[..=end]);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            };
+            let target: Vec<String> = name.split('.').map(str::to_owned).collect();
+            let referenced = self.resolve_at(&target)?;
+            if start == 0 && end + 1 == s.len() {
+                return Ok(referenced);
+            }
+            returned.push_str(&rest[..start]);
+            match referenced {
+                Value::String(referenced) => returned.push_str(&referenced),
+                Value::Number(n) => returned.push_str(&n.to_string()),
+                Value::Bool(b) => returned.push_str(&b.to_string()),
+                _ => bail!(
+                    "Key {:?} cannot be embedded in the string value of {:?}",
+                    name,
+                    path.join(".")
+                ),
+            }
+            rest = &rest[end + 1..];
+        }
+        returned.push_str(rest);
+        Ok(Value::String(returned))
+    }
+}
+
+// Escapes the placeholders of text substituted into the tree, such as environment variables and
+// file contents, so that resolving references gives it back verbatim
+pub(crate) fn escape_placeholders(s: &str) -> String {
+    s.replace("${", "$${")
+}
+
+fn child_path(path: &[String], key: String) -> Vec<String> {
+    let mut returned = path.to_vec();
+    returned.push(key);
+    returned
+}
+
+fn lookup<'a>(root: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(root, |value, part| match value {
+        Value::Object(map) => map.get(part),
+        Value::Array(items) => items.get(part.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn is_reference(name: &str) -> bool {
+    name.split('.').all(|part| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| !c.is_whitespace() && !matches!(c, ':' | '$' | '{' | '}'))
+    })
+}
@@ -0,0 +1,12 @@
+use anyhow::Result;
+use serde_json::Value;
+
+/// A user-defined configuration layer, added through `ConfigLoader::and_source`. Sources are
+/// loaded in their position in the layer stack, and polled again by the watcher
+pub trait Source: Send + Sync {
+    /// Returns the merge patch this source contributes
+    fn load(&self) -> Result<Value>;
+
+    /// Describes the source in error messages
+    fn describe(&self) -> String;
+}
@@ -1,5 +1,14 @@
 mod test_basics;
+mod test_file_values;
+mod test_interpolation;
+#[cfg(unix)]
+mod test_loading_from_commands;
+mod test_loading_from_env;
 mod test_loading_from_files;
+mod test_loading_from_sources;
+mod test_loading_from_urls;
 mod test_loading_overlays;
+mod test_profiles;
+mod test_references;
 mod test_watching_changes;
 mod utils;
@@ -0,0 +1,174 @@
+use super::utils::{file_with, short_sleep, write_atomically};
+use crate::{Config, Format};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Deserialize, Serialize)]
+struct ExampleConfig {
+    user: String,
+    password: String,
+}
+
+#[test]
+fn test_file_values() {
+    let (_, secret_path) = file_with("  s3cr3t\n");
+    let (_, path) = file_with(&format!(
+        "user: admin\npassword: {{$file: {:?}}}",
+        secret_path
+    ));
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().user, "admin");
+    assert_eq!(cfg.get().password, "s3cr3t");
+}
+
+#[test]
+fn test_file_values_are_kept_literal() {
+    let (_, secret_path) = file_with("ab$${cd} ${.user}\n");
+    let (_, path) = file_with(&format!(
+        "user: admin\npassword: {{$file: {:?}}}",
+        secret_path
+    ));
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().password, "ab$${cd} ${.user}");
+}
+
+#[test]
+fn test_file_values_are_kept_as_strings() {
+    let (_, number_path) = file_with("1234\n");
+    let (_, path) = file_with(&format!(
+        r#"{{
+            "untouched": {{"$file": 1}},
+            "nested": [{{"$file": {:?}}}],
+            "other": {{"$file": "unused", "extra": true}}
+        }}"#,
+        number_path
+    ));
+
+    let cfg = Config::<Value>::load_json_file(&path).load().unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "untouched": {"$file": 1},
+            "nested": ["1234"],
+            "other": {"$file": "unused", "extra": true},
+        })
+    );
+}
+
+#[test]
+fn test_file_values_relative_to_declaring_file() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::create_dir(dir.join("secrets")).unwrap();
+    std::fs::write(dir.join("secrets/password"), "relative").unwrap();
+    std::fs::write(
+        dir.join("config.yaml"),
+        "user: admin\npassword: {$file: secrets/password}",
+    )
+    .unwrap();
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(dir.join("config.yaml"))
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().password, "relative");
+}
+
+#[test]
+fn test_file_values_with_env_paths() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::write(dir.join("password"), "from-dir").unwrap();
+    std::env::set_var("FIGURE_TEST_FILE_VALUES_DIR", dir);
+    let (_, path) =
+        file_with("user: admin\npassword: {$file: '${FIGURE_TEST_FILE_VALUES_DIR}/password'}");
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().password, "from-dir");
+}
+
+#[test]
+fn test_file_values_only_read_for_selected_profile() {
+    let (_, secret_path) = file_with("s3cr3t");
+    let (_, path) = file_with(&format!(
+        "user: admin\npassword: none\nprofiles:\n  \
+           prod: {{password: {{$file: {:?}}}}}\n  \
+           staging: {{password: {{$file: /nonexistent/password}}}}",
+        secret_path
+    ));
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&path)
+        .with_profile("prod")
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().password, "s3cr3t");
+}
+
+#[test]
+fn test_missing_file_values() {
+    let (_, path) = file_with("user: admin\npassword: {$file: /nonexistent/password}");
+    let err = Config::<ExampleConfig>::load_yaml_file(&path)
+        .load()
+        .err()
+        .expect("Loading should have failed");
+
+    let message = format!("{:#}", err);
+    assert!(
+        message.contains(r#""/nonexistent/password" referenced by key "password""#),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_file_values_rejected_outside_files() {
+    let (_, secret_path) = file_with("s3cr3t");
+    std::env::set_var(
+        "FIGURE_TEST_FILE_VALUES_ENV__PASSWORD__$file",
+        &*secret_path,
+    );
+
+    for loader in [
+        Config::<Value>::load_str(Format::Yaml, "password: {$file: /etc/hostname}"),
+        Config::<Value>::load_default()
+            .and_overlay_value(json!({"nested": [{"$file": &*secret_path}]})),
+        Config::<Value>::load_default().and_env("FIGURE_TEST_FILE_VALUES_ENV", "__"),
+    ] {
+        let err = loader.load().err().expect("Loading should have failed");
+        assert!(format!("{:#}", err).contains("only supported in configuration files"));
+    }
+}
+
+#[test]
+fn test_watching_file_values() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let secret_path = temp_directory.path().join("password");
+    write_atomically(&secret_path, "first\n");
+    let (_, path) = file_with(&format!(
+        "user: admin\npassword: {{$file: {:?}}}",
+        secret_path
+    ));
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_yaml_file(&path)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().password, "first");
+
+    write_atomically(&secret_path, "rotated\n");
+    short_sleep();
+    assert_eq!(cfg.get().password, "rotated");
+}
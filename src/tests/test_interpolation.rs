@@ -0,0 +1,140 @@
+use super::utils::{file_with, short_sleep};
+use crate::Config;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Deserialize, Serialize)]
+struct ExampleConfig {
+    url: String,
+    port: String,
+}
+
+#[test]
+fn test_env_interpolation() {
+    std::env::set_var("FIGURE_TEST_INTERPOLATION1_HOST", "db.local");
+    std::env::set_var("FIGURE_TEST_INTERPOLATION1_PORT", "5432");
+    std::env::remove_var("FIGURE_TEST_INTERPOLATION1_SCHEME");
+    let (_, path) = file_with(
+        r#"
+url: "${FIGURE_TEST_INTERPOLATION1_SCHEME:-postgres}://${FIGURE_TEST_INTERPOLATION1_HOST}/db"
+port: ${FIGURE_TEST_INTERPOLATION1_PORT}
+"#,
+    );
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().url, "postgres://db.local/db");
+    // expanded values are kept as strings, even when they look like numbers
+    assert_eq!(cfg.get().port, "5432");
+}
+
+#[test]
+fn test_env_interpolation_leaves_other_placeholders() {
+    std::env::set_var("FIGURE_TEST_INTERPOLATION2", "value");
+    let (_, path) = file_with(
+        r#"
+nested: {list: ["${FIGURE_TEST_INTERPOLATION2}", "prefix-${FIGURE_TEST_INTERPOLATION2}"]}
+untouched: ["${server.host}", "${not a variable}", "${unterminated", "$FIGURE_TEST_INTERPOLATION2"]
+"${FIGURE_TEST_INTERPOLATION2}": keys are not expanded
+"#,
+    );
+
+    let cfg = Config::<Value>::load_yaml_file(&path).load().unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "nested": {"list": ["value", "prefix-value"]},
+            "untouched": [
+                "${server.host}",
+                "${not a variable}",
+                "${unterminated",
+                "$FIGURE_TEST_INTERPOLATION2"
+            ],
+            "${FIGURE_TEST_INTERPOLATION2}": "keys are not expanded",
+        })
+    );
+}
+
+#[test]
+fn test_env_interpolation_missing_variables() {
+    std::env::remove_var("FIGURE_TEST_INTERPOLATION3");
+    std::env::set_var("FIGURE_TEST_INTERPOLATION3_EMPTY", "");
+    for (contents, expected) in [
+        (
+            "a: {b: '${FIGURE_TEST_INTERPOLATION3:?database host is required}'}",
+            "database host is required",
+        ),
+        (
+            "a: {b: '${FIGURE_TEST_INTERPOLATION3_EMPTY:?}'}",
+            "FIGURE_TEST_INTERPOLATION3_EMPTY is not set",
+        ),
+    ] {
+        let (_, path) = file_with(contents);
+        let error = Config::<Value>::load_yaml_file(&path).load().err().unwrap();
+
+        let message = format!("{:?}", error);
+        assert!(message.contains(expected), "{}", message);
+        assert!(message.contains("\"a.b\""), "{}", message);
+    }
+}
+
+#[test]
+fn test_watching_reexpands_env() {
+    std::env::set_var("FIGURE_TEST_INTERPOLATION4", "1");
+    let (_, path) = file_with("value: ${FIGURE_TEST_INTERPOLATION4}");
+
+    let (cfg, _watcher) = Config::<Value>::load_yaml_file(&path)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(*cfg.get(), json!({"value": "1"}));
+
+    std::env::set_var("FIGURE_TEST_INTERPOLATION4", "2");
+    short_sleep();
+
+    assert_eq!(*cfg.get(), json!({"value": "2"}));
+}
+
+#[test]
+fn test_env_interpolation_unset_and_escaped_placeholders() {
+    std::env::remove_var("FIGURE_TEST_INTERPOLATION5");
+    std::env::set_var("FIGURE_TEST_INTERPOLATION5_SET", "value");
+    let (_, path) = file_with(
+        r#"
+server: {host: example.com}
+unset: "echo ${FIGURE_TEST_INTERPOLATION5}"
+escaped: "echo $${FIGURE_TEST_INTERPOLATION5_SET} ${FIGURE_TEST_INTERPOLATION5_SET}"
+escaped_reference: "$${.server.host}"
+"#,
+    );
+
+    let cfg = Config::<Value>::load_yaml_file(&path).load().unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "server": {"host": "example.com"},
+            "unset": "echo ${FIGURE_TEST_INTERPOLATION5}",
+            "escaped": "echo ${FIGURE_TEST_INTERPOLATION5_SET} value",
+            "escaped_reference": "${.server.host}",
+        })
+    );
+}
+
+#[test]
+fn test_env_interpolation_keeps_values_literal() {
+    std::env::set_var("FIGURE_TEST_INTERPOLATION6", "p${.secret}x $${y}");
+    let (_, path) = file_with(
+        r#"
+secret: leaked
+password: "${FIGURE_TEST_INTERPOLATION6}"
+"#,
+    );
+
+    let cfg = Config::<Value>::load_yaml_file(&path).load().unwrap();
+
+    assert_eq!(cfg.get()["password"], json!("p${.secret}x $${y}"));
+}
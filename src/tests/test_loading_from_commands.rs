@@ -0,0 +1,106 @@
+use super::utils::{file_with, short_sleep};
+use crate::{config_loader::ConfigLoader, CommandLayer, Format};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{
+    io::{Seek, SeekFrom, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+fn shell(script: &str) -> CommandLayer {
+    CommandLayer::new("sh").arg("-c").arg(script)
+}
+
+#[test]
+fn test_loading_from_command() {
+    let cfg = ConfigLoader::<Value>::new()
+        .and_command(shell("echo 'a: 1'; echo 'b: [2, 3]'").format(Format::Yaml))
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1, "b": [2, 3]}));
+}
+
+#[test]
+fn test_loading_from_command_detects_format() {
+    let cfg = ConfigLoader::<Value>::new()
+        .and_command(CommandLayer::new("echo").args(vec![r#"{"a": 1}"#]))
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+}
+
+#[test]
+fn test_loading_from_failing_command() {
+    let error = ConfigLoader::<Value>::new()
+        .and_command(shell("echo 'a: 1'; echo 'no credentials' >&2; exit 3"))
+        .load()
+        .err()
+        .unwrap();
+
+    let message = format!("{:?}", error);
+    assert!(message.contains("no credentials"), "{}", message);
+    assert!(message.contains("exit status: 3"), "{}", message);
+}
+
+#[test]
+fn test_loading_from_missing_command() {
+    assert!(ConfigLoader::<Value>::new()
+        .and_command(CommandLayer::new("/nonexistent/figure-helper"))
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_loading_from_command_timeout() {
+    let started = Instant::now();
+    let error = ConfigLoader::<Value>::new()
+        .and_command(shell("exec sleep 5").timeout(Duration::from_millis(100)))
+        .load()
+        .err()
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(format!("{:?}", error).contains("timed out"), "{:?}", error);
+}
+
+#[test]
+fn test_loading_from_command_timeout_with_open_output() {
+    let started = Instant::now();
+    let error = ConfigLoader::<Value>::new()
+        .and_command(shell("echo '{}'; sleep 3 &").timeout(Duration::from_millis(200)))
+        .load()
+        .err()
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(format!("{:?}", error).contains("still open"), "{:?}", error);
+}
+
+#[test]
+fn test_watching_command() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_clone = errors.clone();
+    let (mut file, path) = file_with(r#"{"a": 1}"#);
+
+    let (cfg, _watcher) = ConfigLoader::<Value>::new()
+        .and_command(CommandLayer::new("cat").arg(path.to_str().unwrap()))
+        .on_watch_error(move |e| errors_clone.lock().push(format!("{:?}", e)))
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.write_all(br#"{"a": 2}"#).unwrap();
+    file.flush().unwrap();
+    short_sleep();
+    assert_eq!(*cfg.get(), json!({"a": 2}));
+    assert!(errors.lock().is_empty(), "{:?}", errors);
+
+    path.close().unwrap();
+    short_sleep();
+    assert_eq!(*cfg.get(), json!({"a": 2}));
+    assert!(!errors.lock().is_empty());
+}
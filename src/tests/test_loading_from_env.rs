@@ -0,0 +1,192 @@
+use super::utils::{file_with, short_sleep, write_atomically};
+use crate::{config_loader::ConfigLoader, layer::coerce_str_value, Config};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Deserialize, Serialize)]
+struct ExampleConfig {
+    name: String,
+    database: DatabaseConfig,
+}
+
+#[derive(Deserialize, Serialize)]
+struct DatabaseConfig {
+    host: String,
+    port: u16,
+}
+
+#[test]
+fn test_loading_from_env() {
+    std::env::set_var("FIGURE_TEST_ENV1__NAME", "from env");
+    std::env::set_var("FIGURE_TEST_ENV1__DATABASE__HOST", "db.local");
+    std::env::set_var("FIGURE_TEST_ENV1__DATABASE__PORT", "5432");
+    std::env::set_var("FIGURE_TEST_ENV1_UNRELATED", "ignored");
+
+    let cfg = ConfigLoader::<ExampleConfig>::new()
+        .and_env("FIGURE_TEST_ENV1", "__")
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().name, "from env");
+    assert_eq!(cfg.get().database.host, "db.local");
+    assert_eq!(cfg.get().database.port, 5432);
+}
+
+#[test]
+fn test_loading_from_env_with_empty_separator() {
+    std::env::set_var("FIGURE_TEST_ENV_EMPTY_SEPARATOR_AB", "1");
+    let (_, path) = file_with("AB=1");
+
+    for loader in [
+        ConfigLoader::<Value>::new().and_env("FIGURE_TEST_ENV_EMPTY_SEPARATOR", ""),
+        ConfigLoader::<Value>::new().and_overlay_dotenv(&path, "", ""),
+    ] {
+        let message = format!("{:#}", loader.load().err().unwrap());
+        assert!(message.contains("separator"), "{}", message);
+    }
+}
+
+#[test]
+fn test_env_layer_position() {
+    let (_, base_path) = file_with("name: base\ndatabase: {host: base, port: 1}");
+    let (_, overlay_path) = file_with("database: {host: overlay}");
+    std::env::set_var("FIGURE_TEST_ENV2__NAME", "from env");
+    std::env::set_var("FIGURE_TEST_ENV2__DATABASE__HOST", "from env");
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&base_path)
+        .and_env("FIGURE_TEST_ENV2", "__")
+        .and_overlay_yaml(&overlay_path)
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().name, "from env");
+    assert_eq!(cfg.get().database.host, "overlay");
+    assert_eq!(cfg.get().database.port, 1);
+}
+
+#[test]
+fn test_watching_env_changes() {
+    std::env::set_var("FIGURE_TEST_ENV3__VALUE", "1");
+
+    let (cfg, _watcher) = ConfigLoader::<Value>::new()
+        .and_env("FIGURE_TEST_ENV3", "__")
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(*cfg.get(), json!({"value": 1}));
+
+    std::env::set_var("FIGURE_TEST_ENV3__VALUE", "2");
+    short_sleep();
+
+    assert_eq!(*cfg.get(), json!({"value": 2}));
+}
+
+#[test]
+fn test_env_value_coercion() {
+    assert_eq!(coerce_str_value("8080"), json!(8080));
+    assert_eq!(coerce_str_value("-1.5"), json!(-1.5));
+    assert_eq!(coerce_str_value("true"), json!(true));
+    assert_eq!(coerce_str_value("false"), json!(false));
+    assert_eq!(coerce_str_value("[1, 2]"), json!([1, 2]));
+    assert_eq!(coerce_str_value(r#"{"a": 1}"#), json!({"a": 1}));
+    assert_eq!(coerce_str_value(r#""8080""#), json!("8080"));
+    assert_eq!(coerce_str_value("null"), json!("null"));
+    assert_eq!(coerce_str_value("007"), json!("007"));
+    assert_eq!(coerce_str_value("[not json"), json!("[not json"));
+    assert_eq!(coerce_str_value("hello"), json!("hello"));
+}
+
+#[test]
+fn test_loading_dotenv_file() {
+    let (_, path) = file_with(
+        r#"
+# database settings
+APP__DATABASE__HOST=db.local # trailing comment
+export APP__DATABASE__PORT=5432
+APP__NAME="quoted # not a comment \"name\""
+APP__VERSION='1.0'
+APP__DEBUG=true
+OTHER__NAME=ignored
+"#,
+    );
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_overlay_dotenv(&path, "APP", "__")
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "database": {"host": "db.local", "port": 5432},
+            "name": "quoted # not a comment \"name\"",
+            "version": "1.0",
+            "debug": true,
+        })
+    );
+}
+
+#[test]
+fn test_loading_dotenv_file_without_prefix() {
+    let (_, path) = file_with("FOO=1\nDATABASE__HOST=db.local");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_overlay_dotenv(&path, "", "__")
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        json!({"foo": 1, "database": {"host": "db.local"}})
+    );
+}
+
+#[test]
+fn test_loading_invalid_dotenv_file() {
+    for contents in ["APP__NAME", "APP__NAME=\"unterminated", "APP__NAME='a' b"] {
+        let (_, path) = file_with(contents);
+        let error = ConfigLoader::<Value>::new()
+            .and_overlay_dotenv(&path, "APP", "__")
+            .load()
+            .err()
+            .unwrap();
+        assert!(format!("{:?}", error).contains("Line 1"), "{:?}", error);
+    }
+}
+
+#[test]
+fn test_watching_dotenv_changes() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let path = temp_directory.path().join(".env");
+    write_atomically(&path, "APP__VALUE=1");
+
+    let (cfg, _watcher) = ConfigLoader::<Value>::new()
+        .and_overlay_dotenv(&path, "APP", "__")
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(*cfg.get(), json!({"value": 1}));
+
+    write_atomically(&path, "APP__VALUE=2");
+    short_sleep();
+
+    assert_eq!(*cfg.get(), json!({"value": 2}));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_loading_from_env_skips_non_utf8_variables() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    std::env::set_var("FIGURE_TEST_ENV_NON_UTF8__NAME", "valid");
+    std::env::set_var(
+        "FIGURE_TEST_ENV_NON_UTF8__OTHER",
+        OsStr::from_bytes(b"\xff"),
+    );
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_env("FIGURE_TEST_ENV_NON_UTF8", "__")
+        .load();
+    std::env::remove_var("FIGURE_TEST_ENV_NON_UTF8__OTHER");
+
+    assert_eq!(*cfg.unwrap().get(), json!({"name": "valid"}));
+}
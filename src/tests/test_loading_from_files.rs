@@ -1,5 +1,5 @@
-use super::utils::file_with;
-use crate::Config;
+use super::utils::{file_with, gzip, zstd};
+use crate::{config_loader::ConfigLoader, Config};
 
 #[derive(serde::Deserialize, serde::Serialize)]
 struct ExampleConfig {
@@ -24,6 +24,38 @@ fn test_loading_json_file() {
     assert_eq!(value, 2)
 }
 
+#[test]
+fn test_loading_json5_file() {
+    let (_, path) = file_with(
+        r#"
+    // operators like to leave comments
+    {
+        value: 2, /* unquoted keys */
+        extra: ['a', 'b',],
+    }
+    "#,
+    );
+    let cfg = Config::<ExampleConfig>::load_json5_file(&path)
+        .load()
+        .unwrap();
+
+    let value = cfg.get().value;
+    assert_eq!(value, 2)
+}
+
+#[test]
+fn test_loading_invalid_json5_file_mentions_location() {
+    let (_, path) = file_with("{\n  value: 2,\n  extra: [,\n}");
+    let error = Config::<ExampleConfig>::load_json5_file(&path)
+        .load()
+        .err()
+        .unwrap();
+
+    let message = format!("{:?}", error);
+    assert!(message.contains("line 3 column"), "{}", message);
+    assert!(message.contains(&format!("{:?}", &*path)), "{}", message);
+}
+
 #[test]
 fn test_loading_yaml_file() {
     let (_, path) = file_with(
@@ -40,4 +72,362 @@ value: 2
     assert_eq!(value, 2)
 }
 
+#[test]
+fn test_loading_toml_file() {
+    let (_, path) = file_with(
+        r#"
+value = 2
+"#,
+    );
+    let cfg = Config::<ExampleConfig>::load_toml_file(&path)
+        .load()
+        .unwrap();
+
+    let value = cfg.get().value;
+
+    assert_eq!(value, 2)
+}
+
+#[test]
+fn test_loading_toml_file_nested_tables() {
+    let (_, path) = file_with(
+        r#"
+[server]
+host = "localhost"
+ports = [80, 443]
+started = 1979-05-27T07:32:00Z
+"#,
+    );
+    let cfg = Config::<serde_json::Value>::load_toml_file(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({
+            "server": {
+                "host": "localhost",
+                "ports": [80, 443],
+                "started": "1979-05-27T07:32:00Z",
+            }
+        })
+    );
+}
+
+#[test]
+fn test_loading_invalid_toml_file_mentions_path() {
+    let (_, path) = file_with("value = ");
+    let error = Config::<ExampleConfig>::load_toml_file(&path)
+        .load()
+        .err()
+        .unwrap();
+
+    assert!(
+        format!("{error:?}").contains(&format!("{:?}", &*path)),
+        "Unexpected error: {:?}",
+        error
+    );
+}
+
+#[test]
+fn test_loading_file_by_extension() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    for (name, contents) in [
+        ("config.json", r#"{"value": 2}"#),
+        ("config.json5", "{value: 2,}"),
+        ("config.yaml", "value: 2"),
+        ("config.yml", "value: 2"),
+        ("config.toml", "value = 2"),
+        ("config.conf", r#"{"value": 2}"#),
+        ("config", "value: 2"),
+    ] {
+        let path = temp_directory.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        let cfg = Config::<ExampleConfig>::load_file(&path).load().unwrap();
+
+        assert_eq!(cfg.get().value, 2, "Failed loading {}", name);
+    }
+}
+
+#[test]
+fn test_loading_file_with_wrong_extension() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let path = temp_directory.path().join("config.json");
+    std::fs::write(&path, "value: 2").unwrap();
+
+    assert!(Config::<ExampleConfig>::load_file(&path).load().is_err());
+}
+
+#[test]
+fn test_loading_multi_document_yaml_file() {
+    let (_, path) = file_with(
+        r#"
+value: 1
+extra: {a: 1, b: 1}
+---
+# empty documents are skipped
+---
+value: 2
+extra: {b: null}
+"#,
+    );
+    let cfg = Config::<serde_json::Value>::load_yaml_file(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({"value": 2, "extra": {"a": 1}})
+    );
+}
+
+#[test]
+fn test_loading_multi_document_yaml_applies_documents_in_order() {
+    let (_, base_path) = file_with("value: 1\nextra: {a: 1}");
+    // a document removing a key, followed by one setting it again, must not be collapsed
+    // into a single patch before applying it
+    let (_, overlay_path) = file_with("extra: {a: 2}\n---\nextra: null\n---\nextra: {b: 2}");
+    let cfg = Config::<serde_json::Value>::load_yaml_file(&base_path)
+        .and_overlay_yaml(&overlay_path)
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({"value": 1, "extra": {"b": 2}})
+    );
+}
+
+#[test]
+fn test_loading_empty_yaml_overlay() {
+    let (_, base_path) = file_with("value: 1");
+    let (_, empty_path) = file_with("# nothing here\n---\n");
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&base_path)
+        .and_overlay_yaml(&empty_path)
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().value, 1);
+}
+
+#[test]
+fn test_loading_invalid_multi_document_yaml_file() {
+    let (_, path) = file_with("value: 1\n---\nvalue: [");
+
+    assert!(Config::<ExampleConfig>::load_yaml_file(&path)
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_loading_ini_file() {
+    let (_, path) = file_with(
+        r#"
+; top-level keys come before any section
+value = 2
+
+[server]
+host = localhost
+port = 8080
+debug = false
+
+# comments may also start with a hash
+[server.tls]
+enabled = true
+version = "1.2"
+"#,
+    );
+    let cfg = ConfigLoader::<serde_json::Value>::new()
+        .and_overlay_ini(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({
+            "value": 2,
+            "server": {
+                "host": "localhost",
+                "port": 8080,
+                "debug": false,
+                "tls": {"enabled": true, "version": "1.2"},
+            }
+        })
+    );
+}
+
+#[test]
+fn test_loading_ini_file_with_inline_comments() {
+    let (_, path) = file_with(
+        r#"
+port = 80 ; http
+debug = true # for now
+url = http://localhost/#anchor
+quoted = "a ; b" ; comment
+"#,
+    );
+    let cfg = ConfigLoader::<serde_json::Value>::new()
+        .and_overlay_ini(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({
+            "port": 80,
+            "debug": true,
+            "url": "http://localhost/#anchor",
+            "quoted": "a ; b",
+        })
+    );
+}
+
+#[test]
+fn test_loading_invalid_ini_file() {
+    for contents in ["[server", "value"] {
+        let (_, path) = file_with(contents);
+        assert!(ConfigLoader::<serde_json::Value>::new()
+            .and_overlay_ini(&path)
+            .load()
+            .is_err());
+    }
+}
+
+#[test]
+fn test_loading_properties_file() {
+    let (_, path) = file_with(
+        r#"
+# comment
+! another comment
+server.host = localhost
+server.port: 8080
+server.name   long \
+    name
+server.path=C:\\config\u0021
+server.enabled=true
+key\ with\ spaces=1
+"#,
+    );
+    let cfg = ConfigLoader::<serde_json::Value>::new()
+        .and_overlay_properties(&path)
+        .load()
+        .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({
+            "server": {
+                "host": "localhost",
+                "port": 8080,
+                "name": "long name",
+                "path": "C:\\config!",
+                "enabled": true,
+            },
+            "key with spaces": 1,
+        })
+    );
+}
+
+#[test]
+fn test_loading_legacy_formats_by_extension() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    for (name, contents) in [
+        ("config.ini", "value = 2"),
+        ("config.properties", "value=2"),
+    ] {
+        let path = temp_directory.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        let cfg = Config::<ExampleConfig>::load_file(&path).load().unwrap();
+
+        assert_eq!(cfg.get().value, 2, "Failed loading {}", name);
+    }
+}
+
+#[test]
+fn test_loading_compressed_files() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    for (name, contents) in [
+        ("config.json.gz", gzip(r#"{"value": 2}"#)),
+        ("config.yaml.zst", zstd("value: 2")),
+        ("config.gz", gzip("value: 2")),
+    ] {
+        let path = temp_directory.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        let cfg = Config::<ExampleConfig>::load_file(&path).load().unwrap();
+
+        assert_eq!(cfg.get().value, 2, "Failed loading {}", name);
+    }
+
+    let path = temp_directory.path().join("explicit.gz");
+    std::fs::write(&path, gzip("value = 2")).unwrap();
+    let cfg = Config::<ExampleConfig>::load_toml_file(&path)
+        .load()
+        .unwrap();
+    assert_eq!(cfg.get().value, 2);
+}
+
+#[test]
+fn test_loading_corrupt_compressed_file() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let path = temp_directory.path().join("config.json.gz");
+    std::fs::write(&path, r#"{"value": 2}"#).unwrap();
+
+    assert!(Config::<ExampleConfig>::load_file(&path).load().is_err());
+}
+
 //////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_loading_file_with_includes() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::create_dir(dir.join("shared")).unwrap();
+    std::fs::write(dir.join("shared/base.yaml"), "a: base\nb: base\nc: base").unwrap();
+    std::fs::write(
+        dir.join("shared/db.json"),
+        r#"{"$include": "base.yaml", "b": "db", "c": "db"}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("main.yaml"),
+        "$include: [shared/base.yaml, shared/db.json]\nc: main",
+    )
+    .unwrap();
+
+    let cfg = Config::<serde_json::Value>::load_yaml_file(dir.join("main.yaml"))
+        .load()
+        .unwrap();
+    assert_eq!(
+        *cfg.get(),
+        serde_json::json!({"a": "base", "b": "db", "c": "main"})
+    );
+}
+
+#[test]
+fn test_loading_file_with_include_cycle() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::write(dir.join("a.yaml"), "$include: b.yaml\nvalue: 1").unwrap();
+    std::fs::write(dir.join("b.yaml"), "$include: [a.yaml]\nvalue: 2").unwrap();
+
+    let err = Config::<ExampleConfig>::load_yaml_file(dir.join("a.yaml"))
+        .load()
+        .err()
+        .expect("Loading should have failed");
+    let message = format!("{:#}", err);
+    assert!(message.contains("Include cycle detected"), "{}", message);
+    assert!(message.contains("b.yaml"), "{}", message);
+}
+
+#[test]
+fn test_loading_file_with_missing_include() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::write(dir.join("main.yaml"), "$include: missing.yaml\nvalue: 1").unwrap();
+
+    let err = Config::<ExampleConfig>::load_yaml_file(dir.join("main.yaml"))
+        .load()
+        .err()
+        .expect("Loading should have failed");
+    assert!(format!("{:#}", err).contains("Failed loading included file"));
+}
@@ -0,0 +1,77 @@
+use super::utils::{file_with, short_sleep};
+use crate::{config_loader::ConfigLoader, Config, Source};
+use anyhow::{bail, Result};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+
+struct SharedSource(Arc<Mutex<Option<Value>>>);
+
+impl Source for SharedSource {
+    fn load(&self) -> Result<Value> {
+        match &*self.0.lock() {
+            Some(value) => Ok(value.clone()),
+            None => bail!("No value available"),
+        }
+    }
+
+    fn describe(&self) -> String {
+        "shared test source".to_owned()
+    }
+}
+
+#[test]
+fn test_loading_from_source() {
+    let (_, base_path) = file_with("a: 1\nb: 1\nc: 1");
+    let (_, overlay_path) = file_with("c: 3");
+    let value = Arc::new(Mutex::new(Some(json!({"b": 2, "c": 2}))));
+
+    let cfg = Config::<Value>::load_yaml_file(&base_path)
+        .and_source(SharedSource(value))
+        .and_overlay_yaml(&overlay_path)
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1, "b": 2, "c": 3}));
+}
+
+#[test]
+fn test_source_errors_are_described() {
+    let error = ConfigLoader::<Value>::new()
+        .and_source(SharedSource(Default::default()))
+        .load()
+        .err()
+        .unwrap();
+
+    let message = format!("{:?}", error);
+    assert!(message.contains("shared test source"), "{}", message);
+    assert!(message.contains("No value available"), "{}", message);
+}
+
+#[test]
+fn test_watching_source() {
+    let value = Arc::new(Mutex::new(Some(json!({"a": 1}))));
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_clone = errors.clone();
+
+    let (cfg, _watcher) = ConfigLoader::<Value>::new()
+        .and_source(SharedSource(value.clone()))
+        .on_watch_error(move |e| errors_clone.lock().push(format!("{:?}", e)))
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+
+    value.lock().replace(json!({"a": 2}));
+    short_sleep();
+    assert_eq!(*cfg.get(), json!({"a": 2}));
+    assert!(errors.lock().is_empty());
+
+    value.lock().take();
+    short_sleep();
+    assert_eq!(*cfg.get(), json!({"a": 2}));
+    assert!(errors
+        .lock()
+        .iter()
+        .all(|error| error.contains("shared test source")));
+    assert!(!errors.lock().is_empty());
+}
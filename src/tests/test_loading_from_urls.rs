@@ -0,0 +1,302 @@
+use super::utils::http_server_with;
+use super::utils::{file_with, gzip, short_sleep, zstd};
+use crate::{config_loader::ConfigLoader, Format, UrlLayer};
+use axum::http::header::{
+    ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, IF_MODIFIED_SINCE, IF_NONE_MATCH, USER_AGENT,
+};
+use serde_json::{json, Value};
+use std::{
+    io::{Seek, SeekFrom, Write},
+    time::Duration,
+};
+
+#[test]
+fn test_loading_json_url() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    server.set_content_type("application/json");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_json_url(server.url().clone())
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+}
+
+#[test]
+fn test_loading_url_by_content_type() {
+    let server = http_server_with("a: 1\nb: [2, 3]").unwrap();
+    server.set_content_type("application/yaml; charset=utf-8");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_json_url(server.url().clone())
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1, "b": [2, 3]}));
+
+    server.set_contents("a = 1");
+    server.set_content_type("application/toml");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_json_url(server.url().clone())
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+}
+
+#[test]
+fn test_loading_url_with_declared_format() {
+    let server = http_server_with("a: 1").unwrap();
+    server.set_content_type("text/plain");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_yaml_url(server.url().clone())
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+    assert_eq!(server.requests()[0][ACCEPT], "application/yaml");
+
+    server.set_contents("a = 2");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_url(server.url().clone(), Format::Toml)
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 2}));
+    assert_eq!(server.requests()[1][ACCEPT], "application/toml");
+}
+
+#[test]
+fn test_loading_url_with_wrong_declared_format() {
+    let server = http_server_with("a: 1").unwrap();
+    server.set_content_type("text/plain");
+
+    assert!(ConfigLoader::<Value>::new()
+        .and_json_url(server.url().clone())
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_loading_negotiated_url() {
+    let server = http_server_with("a: 1").unwrap();
+    server.set_content_type("text/plain");
+
+    let cfg = ConfigLoader::<Value>::new()
+        .and_negotiated_url(server.url().clone())
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+    let accept = server.requests()[0][ACCEPT].to_str().unwrap().to_owned();
+    for media_type in ["application/json", "application/yaml", "application/toml"] {
+        assert!(accept.contains(media_type), "Unexpected Accept: {}", accept);
+    }
+}
+
+#[test]
+fn test_loading_url_error_status() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+
+    let result = ConfigLoader::<Value>::new()
+        .and_json_url(server.url().join("/missing").unwrap())
+        .load();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_url_layer_headers() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+
+    ConfigLoader::<Value>::new()
+        .and_url_layer(
+            UrlLayer::new(server.url().clone())
+                .header("x-tenant", "tenant-1")
+                .bearer_token("secret")
+                .user_agent("figure-tests"),
+        )
+        .load()
+        .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request["x-tenant"], "tenant-1");
+    assert_eq!(request[AUTHORIZATION], "Bearer secret");
+    assert_eq!(request[USER_AGENT], "figure-tests");
+}
+
+#[test]
+fn test_url_layer_basic_auth() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+
+    ConfigLoader::<Value>::new()
+        .and_url_layer(
+            UrlLayer::new(server.url().clone()).basic_auth("user", Some("pass".to_owned())),
+        )
+        .load()
+        .unwrap();
+
+    // base64 of "user:pass"
+    assert_eq!(server.requests()[0][AUTHORIZATION], "Basic dXNlcjpwYXNz");
+}
+
+#[test]
+fn test_url_layer_bearer_token_file_is_reread() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    let (mut token_file, token_path) = file_with("first-token\n");
+
+    let (_cfg, _watcher) = ConfigLoader::<Value>::new()
+        .and_url_layer(UrlLayer::new(server.url().clone()).bearer_token_file(&token_path))
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(server.requests()[0][AUTHORIZATION], "Bearer first-token");
+
+    token_file.seek(SeekFrom::Start(0)).unwrap();
+    token_file.write_all(b"second-token").unwrap();
+    token_file.flush().unwrap();
+    short_sleep();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests.last().unwrap()[AUTHORIZATION],
+        "Bearer second-token"
+    );
+}
+
+#[test]
+fn test_url_layer_invalid_header() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+
+    assert!(ConfigLoader::<Value>::new()
+        .and_url_layer(UrlLayer::new(server.url().clone()).header("bad header", "value"))
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_url_layer_timeout() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    server.set_delay(Duration::from_millis(500));
+
+    assert!(ConfigLoader::<Value>::new()
+        .and_url_layer(UrlLayer::new(server.url().clone()).timeout(Duration::from_millis(50)))
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_url_layer_conditional_requests_with_etag() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    server.set_etag("\"v1\"");
+
+    let (cfg, _watcher) = ConfigLoader::<Value>::new()
+        .and_json_url(server.url().clone())
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    short_sleep();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+    let requests = server.requests();
+    assert!(requests[0].get(IF_NONE_MATCH).is_none());
+    assert_eq!(requests.last().unwrap()[IF_NONE_MATCH], "\"v1\"");
+    assert!(server.not_modified_responses() > 0);
+
+    server.set_contents(r#"{"a": 2}"#);
+    server.set_etag("\"v2\"");
+    short_sleep();
+
+    assert_eq!(*cfg.get(), json!({"a": 2}));
+    assert_eq!(server.requests().last().unwrap()[IF_NONE_MATCH], "\"v2\"");
+}
+
+#[test]
+fn test_url_layer_conditional_requests_with_last_modified() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    server.set_last_modified("Wed, 21 Oct 2015 07:28:00 GMT");
+
+    let loader = ConfigLoader::<Value>::new().and_json_url(server.url().clone());
+    loader.load().unwrap();
+    let cfg = loader.load().unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 1}));
+    assert_eq!(
+        server.requests()[1][IF_MODIFIED_SINCE],
+        "Wed, 21 Oct 2015 07:28:00 GMT"
+    );
+    assert_eq!(server.not_modified_responses(), 1);
+}
+
+#[test]
+fn test_url_layer_without_validators() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+
+    let loader = ConfigLoader::<Value>::new().and_json_url(server.url().clone());
+    loader.load().unwrap();
+    loader.load().unwrap();
+
+    let request = &server.requests()[1];
+    assert!(request.get(IF_NONE_MATCH).is_none());
+    assert!(request.get(IF_MODIFIED_SINCE).is_none());
+}
+
+#[test]
+fn test_loading_compressed_url() {
+    let server = http_server_with("").unwrap();
+    server.set_encoded_contents(gzip(r#"{"a": 1}"#), "gzip");
+
+    let loader = ConfigLoader::<Value>::new().and_json_url(server.url().clone());
+    assert_eq!(*loader.load().unwrap().get(), json!({"a": 1}));
+    assert_eq!(server.requests()[0][ACCEPT_ENCODING], "gzip, zstd");
+
+    server.set_encoded_contents(zstd(r#"{"a": 2}"#), "zstd");
+    assert_eq!(*loader.load().unwrap().get(), json!({"a": 2}));
+
+    server.set_encoded_contents(b"{}".to_vec(), "br");
+    assert!(loader.load().is_err());
+}
+
+#[test]
+fn test_url_layer_clones_with_other_settings() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    let base = UrlLayer::new(server.url().clone()).user_agent("base");
+
+    let loader = ConfigLoader::<Value>::new().and_url_layer(base.clone());
+    loader.load().unwrap();
+    ConfigLoader::<Value>::new()
+        .and_url_layer(base.header("x-tenant", "tenant-1"))
+        .load()
+        .unwrap();
+
+    let requests = server.requests();
+    assert!(requests[0].get("x-tenant").is_none());
+    assert_eq!(requests[1]["x-tenant"], "tenant-1");
+    assert_eq!(requests[1][USER_AGENT], "base");
+}
+
+#[test]
+fn test_url_layer_clones_do_not_share_validators() {
+    let server = http_server_with(r#"{"a": 1}"#).unwrap();
+    server.set_etag("\"v1\"");
+    let base = UrlLayer::new(server.url().clone());
+
+    ConfigLoader::<Value>::new()
+        .and_url_layer(base.clone().bearer_token("tenant-1"))
+        .load()
+        .unwrap();
+    ConfigLoader::<Value>::new()
+        .and_url_layer(base.clone())
+        .load()
+        .unwrap();
+    ConfigLoader::<Value>::new()
+        .and_url_layer(base.bearer_token("tenant-2"))
+        .load()
+        .unwrap();
+
+    let requests = server.requests();
+    assert!(requests[2].get(IF_NONE_MATCH).is_none());
+    assert_eq!(requests[2][AUTHORIZATION], "Bearer tenant-2");
+}
@@ -1,6 +1,7 @@
 use super::utils::file_with;
-use crate::Config;
+use crate::{config_loader::ConfigLoader, Config, Format};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
 #[derive(Deserialize, Serialize)]
 struct ExampleConfig {
@@ -44,3 +45,282 @@ fn test_overlay_with_default() {
     assert_eq!(cfg.get().name, "");
     assert_eq!(cfg.get().id, 666);
 }
+
+#[test]
+fn test_overrides() {
+    let (_, root_file) = file_with("name: Test\nid: 1");
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&root_file)
+        .and_overrides(vec!["--set", "id=8", "--set=name=Overridden"])
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "Overridden");
+    assert_eq!(inner.id, 8);
+}
+
+#[test]
+fn test_assignments_nested_and_last_wins() {
+    let cfg = ConfigLoader::<Value>::new()
+        .and_assignments(vec![
+            "server.port=80",
+            "server.host=localhost",
+            "server.port=8080",
+            "debug=true",
+            "version=1.0.0",
+            "empty=",
+        ])
+        .load()
+        .unwrap();
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "server": {"host": "localhost", "port": 8080},
+            "debug": true,
+            "version": "1.0.0",
+            "empty": "",
+        })
+    );
+}
+
+#[test]
+fn test_overrides_with_null_remove_keys() {
+    let cfg = Config::<Value>::load_str(Format::Yaml, "a: 1\nb: {c: 2, d: 3}\ne: 4")
+        .and_overrides(vec![
+            "--set",
+            "a=null",
+            "--set=b.c=~",
+            "--set=e=null",
+            "--set=e=5",
+        ])
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"b": {"d": 3}, "e": 5}));
+}
+
+#[test]
+fn test_overrides_from_command_line() {
+    let cfg = ConfigLoader::<Value>::new()
+        .and_overrides(vec![
+            "myprog",
+            "--verbose",
+            "--set",
+            "a=2",
+            "positional=argument",
+            "--set=b=3",
+        ])
+        .load()
+        .unwrap();
+
+    assert_eq!(*cfg.get(), json!({"a": 2, "b": 3}));
+}
+
+#[test]
+fn test_invalid_overrides() {
+    for args in [
+        vec!["--set"],
+        vec!["--set", "no_equals_sign"],
+        vec!["--set==value"],
+    ] {
+        assert!(ConfigLoader::<Value>::new()
+            .and_overrides(args)
+            .load()
+            .is_err());
+    }
+    for assignments in [vec!["no_equals_sign"], vec!["--set", "a=1"], vec!["=value"]] {
+        assert!(ConfigLoader::<Value>::new()
+            .and_assignments(assignments)
+            .load()
+            .is_err());
+    }
+}
+
+#[test]
+fn test_optional_overlays() {
+    let (_, root_file) = file_with("name: Test\nid: 1");
+    let (_, existing_file) = file_with("id: 2");
+    let temp_directory = tempfile::tempdir().unwrap();
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&root_file)
+        .and_optional_overlay_yaml(&existing_file)
+        .and_optional_overlay_yaml(temp_directory.path().join("missing.yaml"))
+        .and_optional_overlay_json(temp_directory.path().join("missing.json"))
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "Test");
+    assert_eq!(inner.id, 2);
+}
+
+#[test]
+fn test_optional_overlay_with_invalid_contents() {
+    let (_, root_file) = file_with("name: Test\nid: 1");
+    let (_, invalid_file) = file_with("{");
+
+    assert!(Config::<ExampleConfig>::load_yaml_file(&root_file)
+        .and_optional_overlay_json(&invalid_file)
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_overlay_dir() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::write(dir.join("10-base.yaml"), "name: base\nid: 1").unwrap();
+    std::fs::write(dir.join("20-id.json"), r#"{"id": 2}"#).unwrap();
+    std::fs::write(dir.join("30-name.yml"), "name: from yml").unwrap();
+    std::fs::write(dir.join("40-ignored.txt"), "not configuration").unwrap();
+    std::fs::write(dir.join(".50-hidden.yaml"), "id: 5").unwrap();
+    std::fs::create_dir(dir.join("60-subdir.yaml")).unwrap();
+
+    let cfg = ConfigLoader::<ExampleConfig>::new()
+        .and_overlay_dir(dir)
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "from yml");
+    assert_eq!(inner.id, 2);
+}
+
+#[test]
+fn test_overlay_missing_dir() {
+    let temp_directory = tempfile::tempdir().unwrap();
+
+    assert!(ConfigLoader::<Value>::new()
+        .and_overlay_dir(temp_directory.path().join("missing"))
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_overlay_embedded_defaults() {
+    const DEFAULTS: &str = "name: Default\nid: 1";
+    let (_, overlay_file) = file_with("id: 3");
+
+    let cfg = Config::<ExampleConfig>::load_str(Format::Yaml, DEFAULTS)
+        .and_overlay_yaml(&overlay_file)
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "Default");
+    assert_eq!(inner.id, 3);
+}
+
+#[test]
+fn test_overlay_invalid_embedded_defaults() {
+    assert!(ConfigLoader::<Value>::new()
+        .and_overlay_str(Format::Json, "{")
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_overlay_value() {
+    let (_, root_file) = file_with("name: Test\nid: 1");
+
+    let cfg = Config::<ExampleConfig>::load_yaml_file(&root_file)
+        .and_overlay_value(json!({"id": 2}))
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "Test");
+    assert_eq!(inner.id, 2);
+}
+
+#[test]
+fn test_mounted_overlays() {
+    let (_, root_file) = file_with("name: Test\nid: 1\ndatabase: {host: base, port: 1}");
+    let (_, db_file) = file_with("host: db.local");
+    let (_, cache_file) = file_with(r#"{"size": 10}"#);
+    let (_, nested_file) = file_with("enabled = true");
+
+    let cfg = Config::<Value>::load_yaml_file(&root_file)
+        .and_overlay_yaml_at("database", &db_file)
+        .and_overlay_json_at("cache", &cache_file)
+        .and_overlay_toml_at("features.new_ui", &nested_file)
+        .load()
+        .unwrap();
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "name": "Test",
+            "id": 1,
+            "database": {"host": "db.local", "port": 1},
+            "cache": {"size": 10},
+            "features": {"new_ui": {"enabled": true}},
+        })
+    );
+}
+
+#[test]
+fn test_overlay_glob() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    std::fs::write(dir.join("b.override.yaml"), "name: from b").unwrap();
+    std::fs::write(
+        dir.join("a.override.json"),
+        r#"{"name": "from a", "id": 1}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("c.yaml"), "id: 3").unwrap();
+
+    let cfg = ConfigLoader::<ExampleConfig>::new()
+        .and_overlay_glob(format!("{}/*.override.*", dir.display()))
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "from b");
+    assert_eq!(inner.id, 1);
+}
+
+#[test]
+fn test_overlay_invalid_glob() {
+    assert!(ConfigLoader::<Value>::new()
+        .and_overlay_glob("/etc/[")
+        .load()
+        .is_err());
+}
+
+#[test]
+fn test_overlay_reader() {
+    let (_, overlay_file) = file_with("id: 3");
+
+    let cfg = ConfigLoader::<ExampleConfig>::new()
+        .and_reader(Format::Yaml, std::io::Cursor::new("name: piped\nid: 1"))
+        .and_overlay_yaml(&overlay_file)
+        .load()
+        .unwrap();
+    let inner = cfg.get();
+    assert_eq!(inner.name, "piped");
+    assert_eq!(inner.id, 3);
+}
+
+#[test]
+fn test_overlay_invalid_reader() {
+    let loader = ConfigLoader::<Value>::new().and_reader(Format::Json, std::io::Cursor::new("{"));
+
+    assert!(loader.load().is_err());
+    // the stream was consumed, but the failure is still reported
+    assert!(loader.load().is_err());
+}
+
+#[test]
+fn test_includes_outside_files() {
+    let error = Config::<Value>::load_str(Format::Yaml, "$include: base.yaml\nvalue: 1")
+        .load()
+        .err()
+        .unwrap();
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains(r#""$include" is only supported in configuration files"#),
+        "{}",
+        message
+    );
+
+    assert!(ConfigLoader::<Value>::new()
+        .and_overlay_value(json!({"$include": ["base.yaml"]}))
+        .load()
+        .is_err());
+}
@@ -0,0 +1,132 @@
+use super::utils::{short_sleep, write_atomically};
+use crate::{Config, Format};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const PROFILES: &str = r#"
+host: localhost
+port: 8080
+database: {name: app, pool: 5}
+profiles:
+  staging: {host: staging.example.com}
+  prod: {host: example.com, port: 80, database: {pool: 50}}
+"#;
+
+#[derive(Deserialize, Serialize)]
+struct ExampleConfig {
+    host: String,
+    port: u16,
+}
+
+#[test]
+fn test_selecting_profile() {
+    let cfg = Config::<Value>::load_str(Format::Yaml, PROFILES)
+        .with_profile("prod")
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.profile().as_deref(), Some("prod"));
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "host": "example.com",
+            "port": 80,
+            "database": {"name": "app", "pool": 50},
+        })
+    );
+}
+
+#[test]
+fn test_profiles_kept_without_selection() {
+    let cfg = Config::<Value>::load_str(Format::Yaml, "host: localhost\nprofiles: [alice, bob]")
+        .load()
+        .unwrap();
+    assert_eq!(cfg.profile(), None);
+    assert_eq!(
+        *cfg.get(),
+        json!({"host": "localhost", "profiles": ["alice", "bob"]})
+    );
+
+    let cfg = Config::<Value>::load_str(Format::Yaml, PROFILES)
+        .load()
+        .unwrap();
+    assert_eq!(cfg.get()["profiles"]["prod"]["host"], "example.com");
+}
+
+#[test]
+fn test_later_layers_override_profiles() {
+    std::env::set_var("FIGURE_TEST_PROFILE3__HOST", "from-env");
+    let cfg = Config::<ExampleConfig>::load_str(Format::Yaml, PROFILES)
+        .and_overrides(vec!["--set", "port=9000"])
+        .and_env("FIGURE_TEST_PROFILE3", "__")
+        .with_profile("prod")
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().port, 9000);
+    assert_eq!(cfg.get().host, "from-env");
+}
+
+#[test]
+fn test_profiles_from_several_layers() {
+    let cfg = Config::<ExampleConfig>::load_str(Format::Yaml, PROFILES)
+        .and_overlay_str(Format::Yaml, "profiles: {staging: {port: 8443}}")
+        .with_profile("staging")
+        .load()
+        .unwrap();
+
+    assert_eq!(cfg.get().host, "staging.example.com");
+    assert_eq!(cfg.get().port, 8443);
+}
+
+#[test]
+fn test_selecting_profile_from_env() {
+    std::env::set_var("FIGURE_TEST_PROFILE1", "staging");
+    let cfg = Config::<ExampleConfig>::load_str(Format::Yaml, PROFILES)
+        .with_profile_from_env("FIGURE_TEST_PROFILE1")
+        .load()
+        .unwrap();
+    assert_eq!(cfg.profile().as_deref(), Some("staging"));
+    assert_eq!(cfg.get().host, "staging.example.com");
+
+    std::env::remove_var("FIGURE_TEST_PROFILE2");
+    let cfg = Config::<ExampleConfig>::load_str(Format::Yaml, PROFILES)
+        .with_profile_from_env("FIGURE_TEST_PROFILE2")
+        .load()
+        .unwrap();
+    assert_eq!(cfg.profile(), None);
+    assert_eq!(cfg.get().host, "localhost");
+}
+
+#[test]
+fn test_selecting_undefined_profile() {
+    let err = Config::<ExampleConfig>::load_str(Format::Yaml, PROFILES)
+        .with_profile("dev")
+        .load()
+        .err()
+        .expect("Loading should have failed");
+
+    assert!(format!("{:#}", err).contains(r#"Profile "dev" is not defined"#));
+}
+
+#[test]
+fn test_watching_profiles() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let path = temp_directory.path().join("config.yaml");
+    write_atomically(&path, PROFILES);
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_yaml_file(&path)
+        .with_profile("prod")
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().host, "example.com");
+
+    write_atomically(
+        &path,
+        PROFILES.replace("host: example.com", "host: new.example.com"),
+    );
+    short_sleep();
+    assert_eq!(cfg.profile().as_deref(), Some("prod"));
+    assert_eq!(cfg.get().host, "new.example.com");
+}
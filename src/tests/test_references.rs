@@ -0,0 +1,139 @@
+use crate::{Config, Format};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Deserialize, Serialize)]
+struct ExampleConfig {
+    server: Server,
+    url: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+#[test]
+fn test_references() {
+    let cfg = Config::<Value>::load_str(
+        Format::Yaml,
+        r#"
+server: {host: localhost, port: 8080, tags: [a, b]}
+url: "http://${.server.host}:${.server.port}/api"
+port: "${.server.port}"
+tags: "${.server.tags}"
+first_tag: "${.server.tags.0}"
+name: app
+title: "${.name} on ${.server.host}"
+literal: "${server.host}"
+chained: "${.links.health}"
+links: {health: "${.links.base}/health", base: "http://${.server.host}"}
+"#,
+    )
+    .load()
+    .unwrap();
+
+    assert_eq!(
+        *cfg.get(),
+        json!({
+            "server": {"host": "localhost", "port": 8080, "tags": ["a", "b"]},
+            "url": "http://localhost:8080/api",
+            "port": 8080,
+            "tags": ["a", "b"],
+            "first_tag": "a",
+            "name": "app",
+            "title": "app on localhost",
+            "literal": "${server.host}",
+            "chained": "http://localhost/health",
+            "links": {"health": "http://localhost/health", "base": "http://localhost"},
+        })
+    );
+}
+
+#[test]
+fn test_references_follow_set_raw() {
+    let cfg = Config::<ExampleConfig>::load_str(
+        Format::Yaml,
+        r#"
+server: {host: localhost, port: 8080}
+url: "http://${.server.host}:${.server.port}/api"
+"#,
+    )
+    .load()
+    .unwrap();
+    assert_eq!(cfg.get().url, "http://localhost:8080/api");
+
+    cfg.set_raw("server.host", "example.com").unwrap();
+    assert_eq!(cfg.get().server.host, "example.com");
+    assert_eq!(cfg.get().url, "http://example.com:8080/api");
+
+    // the raw value keeps the reference rather than its resolved value
+    assert_eq!(
+        cfg.get_raw::<String>("url").unwrap(),
+        "http://${.server.host}:${.server.port}/api"
+    );
+}
+
+#[test]
+fn test_reference_errors() {
+    let err = |contents: &'static str| {
+        Config::<Value>::load_str(Format::Yaml, contents)
+            .load()
+            .err()
+            .map(|e| format!("{:#}", e))
+            .expect("Loading should have failed")
+    };
+
+    let message = err("a: {b: '${.c.d}'}\nc: {d: '${.a.b}'}");
+    assert!(
+        message.contains("Reference cycle detected: a.b -> c.d -> a.b"),
+        "{}",
+        message
+    );
+    let message = err("a: {b: {c: 'x${.a.b}'}}");
+    assert!(
+        message.contains("Reference cycle detected: a.b -> a.b.c -> a.b"),
+        "{}",
+        message
+    );
+    let message = err("a: {b: '${.a.missing}'}");
+    assert!(
+        message.contains(r#"Key "a.missing" referenced by "a.b" does not exist"#),
+        "{}",
+        message
+    );
+    let message = err("a: {b: [1, 2], c: 'x${.a.b}'}");
+    assert!(
+        message.contains(r#"Key "a.b" cannot be embedded in the string value of "a.c""#),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_reference_cycle_rejected_by_set_raw() {
+    let cfg = Config::<Value>::load_str(Format::Yaml, "a: {b: '${.c.d}'}\nc: {d: 1}")
+        .load()
+        .unwrap();
+
+    assert!(cfg.set_raw("c.d", "${.a.b}").is_err());
+    assert_eq!(cfg.get()["a"]["b"], json!(1));
+    assert_eq!(cfg.get_raw::<Value>("c.d").unwrap(), json!(1));
+
+    // the rejected value is not kept, so later changes still apply
+    cfg.set_raw("c.d", 2).unwrap();
+    assert_eq!(cfg.get()["a"]["b"], json!(2));
+}
+
+#[test]
+fn test_references_resolved_by_new_with() {
+    let cfg = Config::new_with(json!({"t": "cost $${x}", "u": "${.t}"})).unwrap();
+    assert_eq!(*cfg.get(), json!({"t": "cost ${x}", "u": "cost ${x}"}));
+
+    cfg.set_raw("v", 1).unwrap();
+    assert_eq!(
+        *cfg.get(),
+        json!({"t": "cost ${x}", "u": "cost ${x}", "v": 1})
+    );
+}
@@ -1,4 +1,4 @@
-use super::utils::{file_with, short_sleep};
+use super::utils::{file_with, gzip, short_sleep, write_atomically};
 use crate::{config_loader::ConfigLoader, Config};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
@@ -50,6 +50,203 @@ fn test_watching_changes_simple_files() {
     assert_eq!(cfg.get().id, 6);
 }
 
+#[test]
+fn test_watching_changes_toml_files() {
+    let (mut root_file, root_path) = file_with("name = \"name\"\nid = 3");
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_toml_file(&root_path)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().name, "name");
+
+    root_file.seek(SeekFrom::Start(0)).unwrap();
+    root_file.set_len(0).unwrap();
+    root_file
+        .write_all("name = \"new_name\"\nid = 4".as_bytes())
+        .unwrap();
+    root_file.flush().unwrap();
+
+    short_sleep();
+
+    assert_eq!(cfg.get().name, "new_name");
+    assert_eq!(cfg.get().id, 4);
+}
+
+#[test]
+fn test_watching_optional_files() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_clone = errors.clone();
+    let (_root_file, root_path) = file_with("name: name\nid: 1");
+    let temp_directory = tempfile::tempdir().unwrap();
+    let optional_path = temp_directory.path().join("local.yaml");
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_yaml_file(&root_path)
+        .and_optional_overlay_yaml(&optional_path)
+        .on_watch_error(move |e| errors_clone.lock().push(format!("{:?}", e)))
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().id, 1);
+
+    write_atomically(&optional_path, "id: 2");
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+
+    std::fs::remove_file(&optional_path).unwrap();
+    short_sleep();
+    assert_eq!(cfg.get().id, 1);
+
+    assert!(errors.lock().is_empty(), "Unexpected errors: {:?}", errors);
+}
+
+#[test]
+fn test_watching_directory() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    write_atomically(dir.join("00-base.yaml"), "name: base\nid: 1");
+
+    let (cfg, _watcher) = ConfigLoader::<ExampleConfig>::new()
+        .and_overlay_dir(dir)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().name, "base");
+
+    write_atomically(dir.join("10-override.json"), r#"{"id": 2}"#);
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+
+    // renaming a fragment changes its position in the merge order
+    write_atomically(dir.join("20-override.yml"), "id: 3");
+    short_sleep();
+    assert_eq!(cfg.get().id, 3);
+    std::fs::rename(dir.join("10-override.json"), dir.join("30-override.json")).unwrap();
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+
+    std::fs::remove_file(dir.join("30-override.json")).unwrap();
+    std::fs::remove_file(dir.join("20-override.yml")).unwrap();
+    short_sleep();
+    assert_eq!(cfg.get().id, 1);
+}
+
+#[test]
+fn test_watching_mounted_files() {
+    #[derive(Deserialize, Serialize)]
+    struct MountingConfig {
+        name: String,
+        nested: ExampleConfig,
+    }
+
+    let (_root_file, root_path) = file_with("name: root");
+    let (mut nested_file, nested_path) = file_with("name: nested\nid: 1");
+
+    let (cfg, _watcher) = Config::<MountingConfig>::load_yaml_file(&root_path)
+        .and_overlay_yaml_at("nested", &nested_path)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().nested.name, "nested");
+
+    cfg.set_raw("nested.name", "overridden").unwrap();
+
+    nested_file.seek(SeekFrom::Start(0)).unwrap();
+    nested_file
+        .write_all("name: nested\nid: 2".as_bytes())
+        .unwrap();
+    nested_file.flush().unwrap();
+    short_sleep();
+
+    assert_eq!(cfg.get().name, "root");
+    assert_eq!(cfg.get().nested.name, "overridden");
+    assert_eq!(cfg.get().nested.id, 2);
+}
+
+#[test]
+fn test_watching_glob() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    let (_root_file, root_path) = file_with("name: name\nid: 1");
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_yaml_file(&root_path)
+        .and_overlay_glob(format!("{}/*.override.yaml", dir.display()))
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().id, 1);
+
+    write_atomically(dir.join("a.override.yaml"), "id: 2");
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+
+    write_atomically(dir.join("b.override.yaml"), "id: 3");
+    short_sleep();
+    assert_eq!(cfg.get().id, 3);
+
+    std::fs::remove_file(dir.join("b.override.yaml")).unwrap();
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+}
+
+#[test]
+fn test_watching_included_files() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let dir = temp_directory.path();
+    write_atomically(dir.join("base.yaml"), "name: base\nid: 1");
+    write_atomically(dir.join("middle.yaml"), "$include: base.yaml");
+    write_atomically(dir.join("main.yaml"), "$include: middle.yaml\nname: main");
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_yaml_file(dir.join("main.yaml"))
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().name, "main");
+    assert_eq!(cfg.get().id, 1);
+
+    write_atomically(dir.join("base.yaml"), "name: base\nid: 2");
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+
+    write_atomically(dir.join("middle.yaml"), "$include: base.yaml\nid: 3");
+    short_sleep();
+    assert_eq!(cfg.get().name, "main");
+    assert_eq!(cfg.get().id, 3);
+}
+
+#[test]
+fn test_watching_compressed_files() {
+    let temp_directory = tempfile::tempdir().unwrap();
+    let path = temp_directory.path().join("config.yaml.gz");
+    write_atomically(&path, gzip("name: name\nid: 1"));
+
+    let (cfg, _watcher) = Config::<ExampleConfig>::load_file(&path)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().id, 1);
+
+    write_atomically(&path, gzip("name: name\nid: 2"));
+    short_sleep();
+    assert_eq!(cfg.get().id, 2);
+}
+
+#[test]
+fn test_watching_does_not_reread_streams() {
+    let (mut overlay_file, overlay_path) = file_with("id: 1");
+
+    let (cfg, _watcher) = ConfigLoader::<ExampleConfig>::new()
+        .and_reader(
+            crate::Format::Yaml,
+            std::io::Cursor::new("name: piped\nid: 0"),
+        )
+        .and_overlay_yaml(&overlay_path)
+        .load_and_watch(Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(cfg.get().name, "piped");
+
+    overlay_file.seek(SeekFrom::Start(0)).unwrap();
+    overlay_file.write_all("id: 2".as_bytes()).unwrap();
+    overlay_file.flush().unwrap();
+    short_sleep();
+
+    assert_eq!(cfg.get().name, "piped");
+    assert_eq!(cfg.get().id, 2);
+}
+
 #[test]
 fn test_watching_changes_files_and_url() {
     let server = super::utils::http_server_with(r#"{}"#).unwrap();
@@ -114,7 +311,7 @@ fn test_watching_changes_with_set_overrides() {
     assert_eq!(cfg.get().id, 6);
 }
 
-#[cfg(any(target_os = "unix", target_os = "macos"))]
+#[cfg(unix)]
 #[test]
 fn test_watching_through_symlinks() {
     // In k8s (for example) side-loaded configuration is implemented as symlinks to an underlying mount.
@@ -132,6 +329,7 @@ fn test_watching_through_symlinks() {
 
     std::fs::OpenOptions::new()
         .create(true)
+        .truncate(true)
         .write(true)
         .open(dir_1.join("file"))
         .unwrap()
@@ -139,6 +337,7 @@ fn test_watching_through_symlinks() {
         .unwrap();
     std::fs::OpenOptions::new()
         .create(true)
+        .truncate(true)
         .write(true)
         .open(dir_2.join("file"))
         .unwrap()
@@ -177,7 +376,10 @@ fn test_watching_changes_errors() {
     short_sleep();
 
     let locked_errors = errors.lock();
-    assert!(locked_errors.len() > 0, "Errors not detected as expected!");
+    assert!(
+        !locked_errors.is_empty(),
+        "Errors not detected as expected!"
+    );
     for error in &*locked_errors {
         assert!(
             format!("{:?}", error)
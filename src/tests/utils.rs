@@ -1,8 +1,15 @@
+use axum::http::{
+    header::{
+        CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    HeaderMap, StatusCode,
+};
 use parking_lot::Mutex;
 use reqwest::Url;
 use std::{
     fs::File,
     io::Write,
+    path::Path,
     sync::{mpsc::channel, Arc},
     time::Duration,
 };
@@ -17,6 +24,25 @@ pub(super) fn file_with(contents: &str) -> (File, TempPath) {
     returned.into_parts()
 }
 
+// writes to a sibling file and renames it over the destination, so that watchers never observe
+// a partially written file
+pub(super) fn write_atomically(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) {
+    let path = path.as_ref();
+    let staging_path = path.with_file_name(".staging");
+    std::fs::write(&staging_path, contents).unwrap();
+    std::fs::rename(&staging_path, path).unwrap();
+}
+
+pub(super) fn gzip(contents: &str) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(contents.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub(super) fn zstd(contents: &str) -> Vec<u8> {
+    zstd::encode_all(contents.as_bytes(), 0).unwrap()
+}
+
 pub(super) fn short_sleep() {
     std::thread::sleep(short_duration())
 }
@@ -27,9 +53,18 @@ fn short_duration() -> Duration {
 
 pub(super) fn http_server_with(contents: impl Into<String>) -> anyhow::Result<MockServer> {
     let (tx, rx) = channel();
-    let contents = Arc::new(Mutex::new(contents.into()));
+    let state = Arc::new(Mutex::new(MockState {
+        contents: contents.into().into_bytes(),
+        content_type: None,
+        content_encoding: None,
+        requests: Vec::new(),
+        delay: None,
+        etag: None,
+        last_modified: None,
+        not_modified_responses: 0,
+    }));
 
-    let contents_clone = contents.clone();
+    let state_clone = state.clone();
 
     std::thread::spawn(|| {
         tokio::runtime::Runtime::new()
@@ -37,7 +72,40 @@ pub(super) fn http_server_with(contents: impl Into<String>) -> anyhow::Result<Mo
             .block_on(async move {
                 let app = axum::Router::new().route(
                     "/contents",
-                    axum::routing::get(move || async move { contents_clone.lock().clone() }),
+                    axum::routing::get(move |request_headers: HeaderMap| async move {
+                        let delay = state_clone.lock().delay;
+                        if let Some(delay) = delay {
+                            std::thread::sleep(delay);
+                        }
+                        let mut state = state_clone.lock();
+                        let matches = |expected: &Option<String>, header| {
+                            expected.as_ref().is_some_and(|expected| {
+                                request_headers.get(header).is_some_and(|v| v == expected)
+                            })
+                        };
+                        let not_modified = matches(&state.etag, IF_NONE_MATCH)
+                            || matches(&state.last_modified, IF_MODIFIED_SINCE);
+                        state.requests.push(request_headers);
+
+                        let mut headers = HeaderMap::new();
+                        if let Some(etag) = &state.etag {
+                            headers.insert(ETAG, etag.parse().unwrap());
+                        }
+                        if let Some(last_modified) = &state.last_modified {
+                            headers.insert(LAST_MODIFIED, last_modified.parse().unwrap());
+                        }
+                        if not_modified {
+                            state.not_modified_responses += 1;
+                            return (StatusCode::NOT_MODIFIED, headers, Vec::new());
+                        }
+                        if let Some(content_type) = &state.content_type {
+                            headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+                        }
+                        if let Some(content_encoding) = &state.content_encoding {
+                            headers.insert(CONTENT_ENCODING, content_encoding.parse().unwrap());
+                        }
+                        (StatusCode::OK, headers, state.contents.clone())
+                    }),
                 );
                 let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
                     .serve(app.into_make_service());
@@ -50,17 +118,66 @@ pub(super) fn http_server_with(contents: impl Into<String>) -> anyhow::Result<Mo
 
     let url = format!("http://{addr}/contents").parse()?;
 
-    Ok(MockServer { contents, url })
+    Ok(MockServer { state, url })
+}
+
+struct MockState {
+    contents: Vec<u8>,
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    requests: Vec<HeaderMap>,
+    delay: Option<Duration>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    not_modified_responses: usize,
 }
 
 pub(super) struct MockServer {
-    contents: Arc<Mutex<String>>,
+    state: Arc<Mutex<MockState>>,
     url: Url,
 }
 
 impl MockServer {
     pub(super) fn set_contents(&self, new_contents: impl Into<String>) {
-        *self.contents.lock() = new_contents.into();
+        let mut state = self.state.lock();
+        state.contents = new_contents.into().into_bytes();
+        state.content_encoding.take();
+    }
+
+    pub(super) fn set_encoded_contents(&self, new_contents: Vec<u8>, content_encoding: &str) {
+        let mut state = self.state.lock();
+        state.contents = new_contents;
+        state.content_encoding.replace(content_encoding.to_owned());
+    }
+
+    pub(super) fn set_content_type(&self, content_type: impl Into<String>) {
+        self.state.lock().content_type.replace(content_type.into());
+    }
+
+    pub(super) fn set_delay(&self, delay: Duration) {
+        self.state.lock().delay.replace(delay);
+    }
+
+    // once set, requests with a matching If-None-Match header get a 304 response
+    pub(super) fn set_etag(&self, etag: impl Into<String>) {
+        self.state.lock().etag.replace(etag.into());
+    }
+
+    // once set, requests with a matching If-Modified-Since header get a 304 response
+    pub(super) fn set_last_modified(&self, last_modified: impl Into<String>) {
+        self.state
+            .lock()
+            .last_modified
+            .replace(last_modified.into());
+    }
+
+    pub(super) fn not_modified_responses(&self) -> usize {
+        self.state.lock().not_modified_responses
+    }
+
+    /// Get the headers of every request received so far
+    pub(super) fn requests(&self) -> Vec<HeaderMap> {
+        self.state.lock().requests.clone()
     }
 
     /// Get a reference to the mock server's url.
@@ -0,0 +1,240 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use parking_lot::Mutex;
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING,
+        CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    StatusCode, Url,
+};
+use serde_json::Value;
+
+use crate::{compression::Compression, Format};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A layer fetching its document over HTTP(S), with control over the request being made
+#[derive(Clone)]
+pub struct UrlLayer {
+    url: Url,
+    // when missing, every supported format is accepted and unlabeled bodies are sniffed
+    format: Option<Format>,
+    headers: Vec<(String, String)>,
+    auth: Option<Auth>,
+    timeout: Duration,
+    user_agent: Option<String>,
+    // built on first use and shared between clones, so that connections are reused across polls
+    client: Arc<OnceLock<Client>>,
+    // the last response carrying validators, revalidated through conditional requests
+    cached: Arc<Mutex<Option<CachedResponse>>>,
+}
+
+struct CachedResponse {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    documents: Vec<Value>,
+}
+
+#[derive(Clone)]
+enum Auth {
+    Bearer(String),
+    BearerFile(PathBuf),
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+impl UrlLayer {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            format: None,
+            headers: Vec::new(),
+            auth: None,
+            timeout: DEFAULT_TIMEOUT,
+            user_agent: None,
+            client: Default::default(),
+            cached: Default::default(),
+        }
+    }
+
+    /// Requests the document in the given format. Responses whose Content-Type names another
+    /// supported format are still parsed as that format
+    pub fn format(mut self, format: Format) -> Self {
+        self.format.replace(format);
+        self.reconfigured()
+    }
+
+    /// Adds a header sent with every request. Invalid header names or values are reported when
+    /// the layer is loaded
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self.reconfigured()
+    }
+
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.auth.replace(Auth::Bearer(token.into()));
+        self.reconfigured()
+    }
+
+    /// Reads the bearer token from a file before every request, so that rotated tokens are
+    /// picked up
+    pub fn bearer_token_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.auth.replace(Auth::BearerFile(path.into()));
+        self.reconfigured()
+    }
+
+    pub fn basic_auth(mut self, username: impl Into<String>, password: Option<String>) -> Self {
+        self.auth.replace(Auth::Basic {
+            username: username.into(),
+            password,
+        });
+        self.reconfigured()
+    }
+
+    /// Sets the timeout of each request, which defaults to 5 seconds
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.reconfigured()
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent.replace(user_agent.into());
+        self.reconfigured()
+    }
+
+    // clones configured differently must not keep using the client built for the original, nor
+    // revalidate the response it got
+    fn reconfigured(mut self) -> Self {
+        self.client = Default::default();
+        self.cached = Default::default();
+        self
+    }
+
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    // Responses carrying an ETag or Last-Modified header are revalidated on the next load, and a
+    // 304 response reuses the previously parsed value
+    pub(crate) fn load(&self) -> Result<Vec<Value>> {
+        let mut request = self.request()?;
+        if let Some(cached) = &*self.cached.lock() {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = request.send()?.error_for_status()?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = &*self.cached.lock() {
+                return Ok(cached.documents.clone());
+            }
+            bail!(
+                "Got {} without having sent a conditional request",
+                resp.status()
+            );
+        }
+
+        let etag = resp.headers().get(ETAG).cloned();
+        let last_modified = resp.headers().get(LAST_MODIFIED).cloned();
+        let documents = self.parse(resp)?;
+        *self.cached.lock() = if etag.is_some() || last_modified.is_some() {
+            Some(CachedResponse {
+                etag,
+                last_modified,
+                documents: documents.clone(),
+            })
+        } else {
+            None
+        };
+        Ok(documents)
+    }
+
+    // A recognized response Content-Type always wins over the declared format, which is only
+    // used to build the Accept header and to parse responses that don't state their format.
+    // Compressed bodies are decoded according to their Content-Encoding
+    fn parse(&self, resp: Response) -> Result<Vec<Value>> {
+        let format = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Format::from_content_type)
+            .or(self.format);
+        let compression = match resp.headers().get(CONTENT_ENCODING) {
+            Some(value) => Compression::from_content_encoding(value.to_str()?)?,
+            None => None,
+        };
+        let mut body = resp.bytes()?.to_vec();
+        if let Some(compression) = compression {
+            body = compression.decompress(&body)?;
+        }
+        let body = String::from_utf8(body)?;
+        match format {
+            Some(format) => format.parse(&body),
+            None => Format::sniff_and_parse(&body),
+        }
+    }
+
+    fn request(&self) -> Result<RequestBuilder> {
+        let accept = match self.format {
+            Some(format) => format.media_type().to_owned(),
+            None => [Format::Json, Format::Yaml, Format::Toml]
+                .iter()
+                .map(|format| format.media_type())
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        let request = self
+            .client()?
+            .get(self.url.clone())
+            .header(ACCEPT, accept)
+            .header(ACCEPT_ENCODING, "gzip, zstd");
+        Ok(match &self.auth {
+            None => request,
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            Some(Auth::BearerFile(path)) => {
+                let token = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed reading bearer token from {path:?}"))?;
+                request.bearer_auth(token.trim())
+            }
+            Some(Auth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+        })
+    }
+
+    // building a client loads the system root certificates, which is too slow to repeat on every poll
+    fn client(&self) -> Result<&Client> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid header name {name:?}"))?,
+                HeaderValue::from_str(value)
+                    .with_context(|| format!("Invalid value for header {name:?}"))?,
+            );
+        }
+        let mut builder = Client::builder()
+            .timeout(self.timeout)
+            .default_headers(headers);
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        let client = builder.build()?;
+        Ok(self.client.get_or_init(|| client))
+    }
+}